@@ -1,7 +1,11 @@
 use futures::TryStreamExt;
-use std::str::FromStr;
+use std::{collections::HashSet, str::FromStr};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
 use dioxus::{logger::tracing::info, prelude::*};
 use serde::{Deserialize, Serialize};
 use sqlx::{
@@ -12,32 +16,147 @@ use sqlx::{
 };
 use tokio::sync::RwLock;
 
+use super::migrations::run_migrations;
 use crate::{
-    views::{DbContact, DbMessage},
-    wallet::utils::{ChatWallet, NETWORK},
-    Route, DB, WALLET,
+    settings::AppSettings,
+    views::{DbContact, DbMessage, DbNode, DbOutboxItem, FINAL_CONFIRMATIONS, MessageStatus},
+    wallet::{
+        events::start_event_dispatcher,
+        message::MessageKind,
+        utils::{ChatWallet, WalletNetwork},
+        wallet_fns::wallet_close,
+    },
+    Route, ACCOUNT, DB, SETTINGS, WALLET,
 };
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
 pub struct DbUserLogin {
+    pub id: i64,
+    // wallet directory name, fixed once created
     pub username: String,
-    pub password: String,
+    // display name of the account
+    pub label: String,
+    // salted argon2 hash, the password itself is never stored
+    pub password_hash: Option<String>,
+    pub network: String,
+    // restored wallets rebuild their chat history once synced
+    pub history_imported: bool,
 }
 
 const DB_URS: &str = "sqlite://userdatabase.db";
 
-pub async fn establish_connection() -> SqlitePool {
-    SqlitePoolOptions::new()
+/// Hashes the password with a random salt into a PHC string, off the UI executor
+pub async fn hash_password(password: &str) -> Result<String> {
+    let password = password.to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow!("Cannot hash password: {e}"))
+    })
+    .await?
+}
+
+/// Checks the password against a stored PHC hash, off the UI executor
+pub async fn verify_password(password: &str, password_hash: &str) -> bool {
+    let (password, password_hash) = (password.to_string(), password_hash.to_string());
+
+    let verified = tokio::task::spawn_blocking(move || match PasswordHash::new(&password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(e) => {
+            info!("Invalid password hash: {e}");
+            false
+        }
+    })
+    .await;
+
+    verified.unwrap_or_else(|e| {
+        info!("Password check failed: {e}");
+        false
+    })
+}
+
+/// Connects to the database and brings its schema up to date
+pub async fn establish_connection() -> Result<SqlitePool> {
+    let db = SqlitePoolOptions::new()
         .max_connections(2)
         .connect_with(
-            SqliteConnectOptions::from_str(DB_URS)
-                .unwrap()
-                .create_if_missing(true),
+            SqliteConnectOptions::from_str(DB_URS)?
+                .create_if_missing(true)
+                // deleted rows, like the plaintext passwords dropped by migration 4, are zeroed
+                .pragma("secure_delete", "ON"),
         )
+        .await?;
+
+    let version = run_migrations(&db).await?;
+    info!("Database schema version: {version}");
+
+    Ok(db)
+}
+
+/// Connects to the database unless the pool is already open
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn db_connect() -> Result<()> {
+    if DB.read().is_none() {
+        let db = establish_connection().await?;
+        *DB.write() = Some(db);
+    }
+
+    Ok(())
+}
+
+/// Reads a stored wallet login
+pub async fn db_read_user(db: &SqlitePool, account_id: i64) -> Result<DbUserLogin, Error> {
+    query_as("SELECT id, username, label, password_hash, network, history_imported FROM user WHERE id = ?1")
+        .bind(account_id)
+        .fetch_one(db)
+        .await
+}
+
+/// Reads every stored wallet login
+pub async fn db_read_users(db: &SqlitePool) -> Result<Vec<DbUserLogin>, Error> {
+    query_as("SELECT id, username, label, password_hash, network, history_imported FROM user ORDER BY id")
+        .fetch_all(db)
         .await
-        .expect("Unable to connect to SQLite database")
 }
 
+/// Renames an account, the wallet directory name stays the same
+pub async fn db_rename_user(db: &SqlitePool, account_id: i64, label: String) -> Result<(), Error> {
+    query("UPDATE user SET label = ?1 WHERE id = ?2")
+        .bind(label)
+        .bind(account_id)
+        .execute(db)
+        .await?;
+
+    info!("Account {account_id} renamed");
+    Ok(())
+}
+
+/// Removes an account with its contacts and message history, the wallet files are kept
+pub async fn db_remove_user(db: &SqlitePool, account_id: i64) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    for sql in [
+        "DELETE FROM Message WHERE account_id = ?1",
+        "DELETE FROM outbox WHERE account_id = ?1",
+        "DELETE FROM contacts WHERE account_id = ?1",
+        "DELETE FROM user WHERE id = ?1",
+    ] {
+        query(sql).bind(account_id).execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+
+    info!("Account {account_id} removed");
+    Ok(())
+}
+
+/// Connects to the database and routes to the unlock screen if a wallet exists
 #[allow(
     clippy::redundant_closure,
     clippy::await_holding_invalid_type,
@@ -47,40 +166,27 @@ pub async fn db_open_wallet() {
     let nav = navigator();
 
     // establish db connection
-    *DB.write() = Some(establish_connection().await);
+    match establish_connection().await {
+        Ok(db) => *DB.write() = Some(db),
+        Err(e) => info!("Database error: {e}"),
+    }
 
     match &*DB.read() {
         Some(db) => {
-            // fetch user db entry
-            let db_user: Result<DbUserLogin, Error> =
-                query_as("SELECT username, password FROM user")
-                    .fetch_one(&*db)
-                    .await;
-
-            // in case there is a user in the db, open their wallet
-            match db_user {
-                Ok(db_user) => {
-                    // try to open the stored wallet
-                    match ChatWallet::open_wallet(
-                        db_user.username,
-                        db_user.password,
-                        NETWORK,
-                        None,
-                        None,
-                    )
-                    .await
-                    {
-                        Ok(wallet) => {
-                            *WALLET.write() = Some(RwLock::new(wallet));
+            // load the app settings
+            *SETTINGS.write() = db_read_settings(db).await;
 
-                            info!("Wallet opened");
-                            nav.push(Route::Home {});
-                        }
-                        Err(_) => {
-                            info!("Wallet couldn't be opened");
-                            nav.push(Route::RestoreWalletOptions {});
-                        }
+            // in case there are users in the db, ask for a password
+            match db_read_users(db).await {
+                Ok(db_users) if !db_users.is_empty() => {
+                    if ACCOUNT.read().is_none() {
+                        *ACCOUNT.write() = Some(db_users[0].id);
                     }
+
+                    nav.push(Route::Unlock {});
+                }
+                Ok(_) => {
+                    nav.push(Route::RestoreWalletOptions {});
                 }
                 Err(e) => {
                     info!("Wallet error: {e}");
@@ -95,6 +201,114 @@ pub async fn db_open_wallet() {
     }
 }
 
+/// Opens the stored wallet with the password entered on the unlock screen
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+pub async fn db_unlock_wallet(
+    account_id: i64,
+    password: String,
+    unlock_msg: &mut Signal<String>,
+) {
+    let nav = navigator();
+
+    // the pool is closed while the session is locked
+    if let Err(e) = db_connect().await {
+        info!("Database error: {e}");
+        unlock_msg.set(format!("Database error: {e}"));
+        return;
+    }
+
+    match &*DB.read() {
+        Some(db) => {
+            let db_user = match db_read_user(db, account_id).await {
+                Ok(db_user) => db_user,
+                Err(e) => {
+                    info!("Wallet error: {e}");
+                    nav.push(Route::RestoreWalletOptions {});
+                    return;
+                }
+            };
+
+            // fail fast on a wrong password without touching the wallet files
+            if let Some(password_hash) = &db_user.password_hash {
+                if !verify_password(&password, password_hash).await {
+                    info!("Incorrect password entered");
+                    unlock_msg.set("Incorrect password entered".to_string());
+                    return;
+                }
+            }
+
+            let network = match db_user.network.parse::<WalletNetwork>() {
+                Ok(network) => network,
+                Err(e) => {
+                    info!("Stored wallet network error: {e}");
+                    unlock_msg.set(e.to_string());
+                    return;
+                }
+            };
+
+            let settings = SETTINGS.read().clone();
+
+            // try to open the stored wallet
+            match ChatWallet::open_wallet(
+                db_user.username.clone(),
+                password.clone(),
+                network,
+                None,
+                Some(settings.table_size),
+                settings.n_threads,
+            )
+            .await
+            {
+                Ok(wallet) => {
+                    // logins migrated from the plaintext schema get their verifier now
+                    if db_user.password_hash.is_none() {
+                        db_store_password_hash(db, db_user.id, &password).await;
+                    }
+
+                    *WALLET.write() = Some(RwLock::new(wallet));
+                    *ACCOUNT.write() = Some(db_user.id);
+                    start_event_dispatcher();
+
+                    info!("Wallet opened on {network}");
+                    nav.push(Route::Home {});
+                }
+                Err(e) => {
+                    info!("Wallet couldn't be opened: {e:#}");
+                    unlock_msg.set(format!("Wallet couldn't be opened: {e}"));
+                }
+            }
+        }
+        None => {
+            nav.push(Route::RestoreWalletOptions {});
+        }
+    }
+}
+
+/// Stores the password verifier of a wallet login
+pub async fn db_store_password_hash(db: &SqlitePool, account_id: i64, password: &str) {
+    let password_hash = match hash_password(password).await {
+        Ok(password_hash) => password_hash,
+        Err(e) => {
+            info!("{e}");
+            return;
+        }
+    };
+
+    match query("UPDATE user SET password_hash = ?1 WHERE id = ?2")
+        .bind(password_hash)
+        .bind(account_id)
+        .execute(db)
+        .await
+    {
+        Ok(_) => info!("Password verifier stored"),
+        Err(e) => info!("{e}"),
+    }
+}
+
 #[allow(
     clippy::redundant_closure,
     clippy::await_holding_invalid_type,
@@ -103,111 +317,127 @@ pub async fn db_open_wallet() {
 pub async fn db_restore_wallet(
     name: String,
     password: String,
+    network: WalletNetwork,
     seed: Option<String>,
     private_key: Option<String>,
 ) {
     let nav = navigator();
+    let settings = SETTINGS.read().clone();
+
+    // the pool is closed when adding an account from the unlock screen
+    if let Err(e) = db_connect().await {
+        info!("Database error: {e}");
+        return;
+    }
+
+    // only restored wallets have a history to import
+    let history_imported = seed.is_none() && private_key.is_none();
 
     // try to create the requested one
     match ChatWallet::create_wallet(
         name.clone(),
         password.clone(),
-        NETWORK,
+        network,
         seed,
         private_key,
         None,
-        None,
+        Some(settings.table_size),
+        settings.n_threads,
     )
     .await
     {
         Ok(wallet) => {
             match &*DB.read() {
                 Some(db) => {
-                    // create base table if it does not exist
-                    query(
-                "CREATE TABLE IF NOT EXISTS user ( username TEXT NOT NULL, password TEXT NOT NULL )",
-            )
-            .execute(&*db)
-            .await
-            .expect("Cannot create DB");
+                    let password_hash = match hash_password(&password).await {
+                        Ok(password_hash) => password_hash,
+                        Err(e) => {
+                            info!("{e}");
+                            close_new_wallet(wallet).await;
+                            return;
+                        }
+                    };
 
-                    // store the login info in the database
-                    query("INSERT INTO user (username, password) VALUES (?1, ?2)")
-                        .bind(name)
-                        .bind(password)
-                        .execute(&*db)
-                        .await
-                        .expect("Error storing the login info in the database.");
+                    // store the login info in the database, without the password
+                    match query(
+                        "INSERT INTO user (username, label, password_hash, network, history_imported) VALUES (?1, ?1, ?2, ?3, ?4)",
+                    )
+                    .bind(name)
+                    .bind(password_hash)
+                    .bind(network.as_str())
+                    .bind(history_imported)
+                    .execute(&*db)
+                    .await
+                    {
+                        Ok(result) => {
+                            // the new account replaces the open wallet once it is ready
+                            wallet_close().await;
 
-                    // use the new wallet instance as the app state wallet
-                    *WALLET.write() = Some(RwLock::new(wallet));
+                            // use the new wallet instance as the app state wallet
+                            *WALLET.write() = Some(RwLock::new(wallet));
+                            *ACCOUNT.write() = Some(result.last_insert_rowid());
+                            start_event_dispatcher();
 
-                    info!("Wallet created/restored successfully");
-                    nav.push(Route::Home {});
+                            info!("Wallet created/restored successfully");
+                            nav.push(Route::Home {});
+                        }
+                        Err(e) => {
+                            info!("Error storing the login info in the database: {e}");
+                            close_new_wallet(wallet).await;
+                        }
+                    }
                 }
                 None => {
                     info!("DB openning error");
+                    close_new_wallet(wallet).await;
                 }
             }
         }
 
+        // the open wallet stays open
         Err(e) => {
             info!("Error creating/restoring wallet: {e}");
         }
     }
 }
 
+// Closes a wallet created for an account that could not be stored, the open one is kept
+async fn close_new_wallet(wallet: ChatWallet) {
+    if let Err(e) = wallet.close_wallet().await {
+        info!("close_wallet error: {e}");
+    }
+}
+
 #[allow(
     clippy::redundant_closure,
     clippy::await_holding_invalid_type,
     clippy::borrow_deref_ref
 )]
 pub async fn db_store_init_message(message: DbMessage) {
+    let account_id = *ACCOUNT.read();
+
     match &*DB.read() {
         Some(db) => {
-            // create base table if it does not exist
-            query(
-                "CREATE TABLE IF NOT EXISTS contacts ( name TEXT NOT NULL, address TEXT NOT NULL )",
+            // add the address as a contact if it is not one yet
+            match query(
+                "INSERT OR IGNORE INTO contacts (name, address, account_id) VALUES (?1, ?2, ?3)",
             )
+            .bind(message.address.clone().as_str())
+            .bind(message.address.clone().as_str())
+            .bind(account_id)
             .execute(&*db)
-            .await
-            .expect("Cannot create contacts DB");
-
-            // get all the contacts
-            let all_contacts: Result<Vec<DbContact>, Error> = query_as("SELECT * FROM contacts")
-                .fetch(&*db)
-                .try_collect()
-                .await;
-
-            match all_contacts {
-                Ok(all_contacts_vec) => {
-                    // check if the address is not contained
-                    if all_contacts_vec
-                        .iter()
-                        .any(|contact| message.address != contact.address)
-                    {
-                        match query("INSERT INTO contacts (name, address) VALUES (?1, ?2)")
-                            .bind(message.address.clone().as_str())
-                            .bind(message.address.clone().as_str())
-                            .execute(&*db)
-                            .await
-                        {
-                            Ok(_) => {
-                                info!("Contact successfully added");
-                                db_store_msg(db, message).await;
-                            }
-                            Err(e) => {
-                                info!("Error adding contact to db: {e}");
-                                format!("Error adding contact to db: {}", e).to_string();
-                            }
-                        }
+                .await
+            {
+                Ok(result) => {
+                    if result.rows_affected() > 0 {
+                        info!("Contact successfully added");
                     } else {
                         info!("Contact already exists, adding the message");
-                        db_store_msg(db, message).await;
                     }
+                    db_store_msg(db, account_id, message).await;
                 }
                 Err(e) => {
-                    info!("{e}");
+                    info!("Error adding contact to db: {e}");
                 }
             }
         }
@@ -217,31 +447,47 @@ pub async fn db_store_init_message(message: DbMessage) {
     }
 }
 
-#[allow(clippy::await_holding_invalid_type, clippy::borrow_deref_ref)]
-async fn db_store_msg(db: &SqlitePool, message: DbMessage) {
-    // create Message table if it does not exist
-    query(
-        "CREATE TABLE IF NOT EXISTS
-             Message (
-                 id INTEGER PRIMARY KEY AUTOINCREMENT,
-                 status TEXT NOT NULL,
-                 direction TEXT NOT NULL,
-                 address TEXT NOT NULL,
-                 hash TEXT,
-                 fee REAL,
-                 timestamp INTEGER NOT NULL,
-                 topoheight INTEGER NOT NULL,
-                 asset TEXT NOT NULL,
-                 amount INTEGER NOT NULL,
-                 message TEXT
-             )",
+// Statuses a message can leave for `next`, as an SQL list
+fn status_sources(next: MessageStatus) -> String {
+    MessageStatus::ALL
+        .iter()
+        .filter(|from| from.can_become(next))
+        .map(|from| format!("'{from:?}'"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Every status update goes through this query, a message whose status cannot become `next`
+// is left as is. `?1` binds `next`, the other columns and the filter bind from `?2`.
+fn status_update(next: MessageStatus, columns: &str, filter: &str) -> String {
+    format!(
+        "UPDATE Message SET status = ?1{columns} WHERE ({filter}) AND status IN ({})",
+        status_sources(next)
     )
-    .execute(&*db)
-    .await
-    .expect("Cannot create Messages DB");
+}
+
+/// Stores a message, or updates the row already holding it: the same local id, or the same
+/// transfer of the same transaction when an event is replayed
+#[allow(clippy::await_holding_invalid_type, clippy::borrow_deref_ref)]
+async fn db_store_msg(db: &SqlitePool, account_id: Option<i64>, message: DbMessage) {
+    // messages built without an envelope are plain text
+    let kind = if message.kind.is_empty() {
+        MessageKind::Text.as_str().to_string()
+    } else {
+        message.kind.clone()
+    };
+
+    let local_id = if message.local_id.is_empty() {
+        DbMessage::new_local_id()
+    } else {
+        message.local_id.clone()
+    };
+
+    // an update follows the lifecycle of the stored message
+    let sources = status_sources(message.status);
 
     // store Message query
-    match query(
+    match query(&format!(
         "INSERT INTO
              Message (
                  status,
@@ -253,9 +499,33 @@ async fn db_store_msg(db: &SqlitePool, message: DbMessage) {
                  topoheight,
                  asset,
                  amount,
-                 message
-             ) VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10 )",
-    )
+                 message,
+                 account_id,
+                 message_id,
+                 reply_to,
+                 kind,
+                 request_amount,
+                 request_asset,
+                 request_expires_at,
+                 dev_fee,
+                 transfer_index,
+                 local_id
+             ) VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20 )
+         ON CONFLICT(local_id) DO UPDATE SET
+             status = excluded.status,
+             hash = excluded.hash,
+             fee = excluded.fee,
+             timestamp = excluded.timestamp,
+             topoheight = excluded.topoheight,
+             dev_fee = excluded.dev_fee,
+             transfer_index = excluded.transfer_index
+         WHERE Message.status IN ({sources})
+         ON CONFLICT(account_id, hash, transfer_index) WHERE hash IS NOT NULL AND hash != '' DO UPDATE SET
+             status = excluded.status,
+             timestamp = excluded.timestamp,
+             topoheight = excluded.topoheight
+         WHERE Message.status IN ({sources})",
+    ))
     .bind(message.status)
     .bind(message.direction)
     .bind(message.address)
@@ -266,6 +536,16 @@ async fn db_store_msg(db: &SqlitePool, message: DbMessage) {
     .bind(message.asset)
     .bind(message.amount)
     .bind(message.message.as_deref())
+    .bind(account_id)
+    .bind(message.message_id)
+    .bind(message.reply_to)
+    .bind(kind)
+    .bind(message.request_amount)
+    .bind(message.request_asset)
+    .bind(message.request_expires_at)
+    .bind(message.dev_fee)
+    .bind(message.transfer_index)
+    .bind(local_id)
     .execute(&*db)
     .await
     {
@@ -276,15 +556,23 @@ async fn db_store_msg(db: &SqlitePool, message: DbMessage) {
 
 #[allow(clippy::await_holding_invalid_type, clippy::borrow_deref_ref)]
 pub async fn db_update_status_fee(message: DbMessage) {
+    let account_id = *ACCOUNT.read();
+
     match &*DB.read() {
         Some(db) => {
             // update Message query
-            match query("UPDATE Message SET status = ?1, hash = ?2, fee = ?3 WHERE topoheight = ?4 AND message = ?5")
+            // several messages with the same text can be in flight, match on the local id
+            let sql = status_update(
+                message.status,
+                ", hash = ?2, fee = ?3",
+                "local_id = ?4 AND account_id = ?5",
+            );
+            match query(&sql)
                 .bind(message.status)
                 .bind(message.hash)
                 .bind(message.fee)
-                .bind(message.topoheight)
-                .bind(message.message)
+                .bind(message.local_id)
+                .bind(account_id)
                 .execute(&*db)
                 .await
             {
@@ -305,18 +593,25 @@ pub async fn db_update_status_fee(message: DbMessage) {
     clippy::borrow_deref_ref
 )]
 pub async fn db_update_status_topoheight(message: DbMessage) {
+    let account_id = *ACCOUNT.read();
+
     match &*DB.read() {
         Some(db) => {
             // update Message query
-            match query(
-                "UPDATE Message SET status = ?1, topoheight = ?2, timestamp = ?3 WHERE hash = ?4",
-            )
-            .bind(message.status)
-            .bind(message.topoheight)
-            .bind(message.timestamp)
-            .bind(message.hash)
-            .execute(&*db)
-            .await
+            // the chain only knows the hash, every message of the transaction is confirmed
+            let sql = status_update(
+                message.status,
+                ", topoheight = ?2, timestamp = ?3",
+                "hash = ?4 AND account_id = ?5",
+            );
+            match query(&sql)
+                .bind(message.status)
+                .bind(message.topoheight)
+                .bind(message.timestamp)
+                .bind(message.hash)
+                .bind(account_id)
+                .execute(&*db)
+                .await
             {
                 Ok(_) => info!("Message status and topoheight updated successufully in db"),
                 Err(e) => info!("{e}"),
@@ -339,10 +634,13 @@ pub async fn db_read_messages(
     address: String,
     messages_from_db: &mut Signal<Vec<DbMessage>>,
 ) {
+    let account_id = *ACCOUNT.read();
+
     let db_messages: Result<Vec<DbMessage>, Error> = query_as(
-                 "SELECT status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message FROM Message WHERE address = ?",
+                 "SELECT local_id, status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message, message_id, reply_to, kind, request_amount, request_asset, request_expires_at, dev_fee, transfer_index FROM Message WHERE address = ?1 AND account_id = ?2",
              )
              .bind(address)
+             .bind(account_id)
              .fetch_all(&*db)
              .await;
 
@@ -356,27 +654,6 @@ pub async fn db_read_messages(
         }
         Err(e) => {
             info!("{}", e);
-
-            // create Message table if it does not exist
-            query(
-                "CREATE TABLE IF NOT EXISTS
-             Message (
-                 id INTEGER PRIMARY KEY AUTOINCREMENT,
-                 status TEXT NOT NULL,
-                 direction TEXT NOT NULL,
-                 address TEXT NOT NULL,
-                 hash TEXT,
-                 fee REAL,
-                 timestamp INTEGER NOT NULL,
-                 topoheight INTEGER NOT NULL,
-                 asset TEXT NOT NULL,
-                 amount INTEGER NOT NULL,
-                 message TEXT
-             )",
-            )
-            .execute(&*db)
-            .await
-            .expect("Cannot create Messages DB");
         }
     }
 }
@@ -390,16 +667,14 @@ pub async fn db_add_contact(
     contact_ret_msg: &mut Signal<String>,
     new_contact: DbContact,
 ) {
-    // create base table if it does not exist
-    query("CREATE TABLE IF NOT EXISTS contacts ( name TEXT NOT NULL, address TEXT NOT NULL )")
-        .execute(&*db)
-        .await
-        .expect("Cannot create contacts DB");
+    let account_id = *ACCOUNT.read();
 
-    let all_contacts: Result<Vec<DbContact>, Error> = query_as("SELECT * FROM contacts")
-        .fetch(&*db)
-        .try_collect()
-        .await;
+    let all_contacts: Result<Vec<DbContact>, Error> =
+        query_as("SELECT name, address FROM contacts WHERE account_id = ?1")
+            .bind(account_id)
+            .fetch(&*db)
+            .try_collect()
+            .await;
 
     match all_contacts {
         Ok(all_contacts_vec) => {
@@ -413,10 +688,13 @@ pub async fn db_add_contact(
                     .iter()
                     .any(|contact| contact.address == new_contact.address.clone())
                 {
-                    match query("INSERT INTO contacts (name, address) VALUES (?1, ?2)")
-                        .bind(new_contact.name.as_str())
-                        .bind(new_contact.address.as_str())
-                        .execute(&*db)
+                    match query(
+                        "INSERT INTO contacts (name, address, account_id) VALUES (?1, ?2, ?3)",
+                    )
+                    .bind(new_contact.name.as_str())
+                    .bind(new_contact.address.as_str())
+                    .bind(account_id)
+                    .execute(&*db)
                         .await
                     {
                         Ok(_) => {
@@ -454,15 +732,18 @@ pub async fn db_add_contact(
 )]
 pub async fn db_remove_contact(db: &SqlitePool, address: String) {
     let nav = navigator();
+    let account_id = *ACCOUNT.read();
 
-    match query("DELETE FROM Message WHERE address = ?1")
+    match query("DELETE FROM Message WHERE address = ?1 AND account_id = ?2")
         .bind(address.clone())
+        .bind(account_id)
         .execute(&*db)
         .await
     {
         Ok(_) => {
-            match query("DELETE FROM contacts WHERE address = ?1")
+            match query("DELETE FROM contacts WHERE address = ?1 AND account_id = ?2")
                 .bind(address)
+                .bind(account_id)
                 .execute(&*db)
                 .await
             {
@@ -476,8 +757,9 @@ pub async fn db_remove_contact(db: &SqlitePool, address: String) {
         }
         Err(e) => {
             info!("{}", e);
-            match query("DELETE FROM contacts WHERE address = ?1")
+            match query("DELETE FROM contacts WHERE address = ?1 AND account_id = ?2")
                 .bind(address)
+                .bind(account_id)
                 .execute(&*db)
                 .await
             {
@@ -498,18 +780,19 @@ pub async fn db_remove_contact(db: &SqlitePool, address: String) {
     clippy::borrow_deref_ref
 )]
 pub async fn db_read_contacts(db: &SqlitePool, contacts_vec: &mut Signal<Vec<DbContact>>) {
-    // create base table if it does not exist
-    query("CREATE TABLE IF NOT EXISTS contacts ( name TEXT NOT NULL, address TEXT NOT NULL )")
-        .execute(&*db)
-        .await
-        .expect("Cannot create contacts DB");
+    let account_id = *ACCOUNT.read();
 
-    let db_contacts: Result<Vec<DbContact>, Error> = query_as("SELECT name, address FROM contacts")
-        .fetch_all(&*db)
-        .await;
+    let db_contacts: Result<Vec<DbContact>, Error> =
+        query_as("SELECT name, address FROM contacts WHERE account_id = ?1")
+            .bind(account_id)
+            .fetch_all(&*db)
+            .await;
 
     match db_contacts {
         Ok(mut db_contacts) => {
+            // the list is read again after every change
+            contacts_vec.write().clear();
+
             while let Some(contact_from_db) = db_contacts.pop() {
                 info!("{contact_from_db:?}");
                 contacts_vec.write().push(DbContact {
@@ -523,3 +806,688 @@ pub async fn db_read_contacts(db: &SqlitePool, contacts_vec: &mut Signal<Vec<DbC
         }
     }
 }
+
+/// Reads the outgoing messages with a transaction that did not reach a final status yet
+pub async fn db_read_pending_messages(
+    db: &SqlitePool,
+    account_id: i64,
+) -> Result<Vec<DbMessage>, Error> {
+    query_as(
+        "SELECT local_id, status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message, message_id, reply_to, kind, request_amount, request_asset, request_expires_at, dev_fee, transfer_index FROM Message WHERE account_id = ?1 AND direction = 'Outgoing' AND status IN ('Built', 'Broadcast', 'InMempool') AND hash IS NOT NULL AND hash != ''",
+    )
+    .bind(account_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Sums the dev fees paid with the broadcast messages of an account, in XEL
+pub async fn db_total_dev_fees(db: &SqlitePool, account_id: i64) -> Result<f64, Error> {
+    let (total,): (f64,) = query_as(
+        "SELECT COALESCE(SUM(dev_fee), 0.0) FROM Message WHERE account_id = ?1 AND direction = 'Outgoing' AND hash IS NOT NULL AND hash != '' AND status != 'Failed'",
+    )
+    .bind(account_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(total)
+}
+
+/// Reads an outgoing message by its local id
+pub async fn db_read_message(
+    db: &SqlitePool,
+    account_id: i64,
+    local_id: &str,
+) -> Result<DbMessage, Error> {
+    query_as(
+        "SELECT local_id, status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message, message_id, reply_to, kind, request_amount, request_asset, request_expires_at, dev_fee, transfer_index FROM Message WHERE account_id = ?1 AND local_id = ?2 AND direction = 'Outgoing'",
+    )
+    .bind(account_id)
+    .bind(local_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Queues a message in the outbox, a message already there is due again at the given time.
+/// The hash of a transaction already tried with the message is kept, the worker checks it first.
+pub async fn db_enqueue_outbox(
+    db: &SqlitePool,
+    account_id: i64,
+    message: &DbMessage,
+    next_attempt_at: i64,
+    tx_hash: Option<String>,
+    last_error: Option<String>,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    let queued = query(&status_update(
+        MessageStatus::Queued,
+        ", hash = ?2, transfer_index = ?3",
+        "account_id = ?4 AND local_id = ?5",
+    ))
+    .bind(MessageStatus::Queued)
+    .bind(message.hash.as_str())
+    .bind(message.transfer_index)
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    // a message that went through cannot be queued again
+    if queued.rows_affected() == 0 {
+        return Err(Error::RowNotFound);
+    }
+
+    query(
+        "INSERT INTO outbox (account_id, local_id, attempts, next_attempt_at, tx_hash, last_error) VALUES (?1, ?2, 0, ?3, ?4, ?5)
+         ON CONFLICT(account_id, local_id) DO UPDATE SET attempts = 0, next_attempt_at = excluded.next_attempt_at, tx_hash = excluded.tx_hash, last_error = excluded.last_error",
+    )
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .bind(next_attempt_at)
+    .bind(tx_hash)
+    .bind(last_error)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    info!("Message {} queued in the outbox", message.local_id);
+    Ok(())
+}
+
+/// Reads the outbox messages due for an attempt, oldest first
+pub async fn db_read_due_outbox(
+    db: &SqlitePool,
+    account_id: i64,
+    now: i64,
+) -> Result<Vec<DbOutboxItem>, Error> {
+    query_as(
+        "SELECT local_id, attempts, next_attempt_at, tx_hash, last_error FROM outbox WHERE account_id = ?1 AND next_attempt_at <= ?2 ORDER BY id",
+    )
+    .bind(account_id)
+    .bind(now)
+    .fetch_all(db)
+    .await
+}
+
+/// Deadline of the batch window still open, the first attempt of the messages queued in it
+pub async fn db_outbox_open_window(
+    db: &SqlitePool,
+    account_id: i64,
+    now: i64,
+) -> Result<Option<i64>, Error> {
+    let (deadline,): (Option<i64>,) = query_as(
+        "SELECT MIN(next_attempt_at) FROM outbox WHERE account_id = ?1 AND attempts = 0 AND tx_hash IS NULL AND next_attempt_at > ?2",
+    )
+    .bind(account_id)
+    .bind(now)
+    .fetch_one(db)
+    .await?;
+
+    Ok(deadline)
+}
+
+/// Records a failed attempt and when to try again, with the transaction it tried
+pub async fn db_outbox_retry_later(
+    db: &SqlitePool,
+    account_id: i64,
+    message: &DbMessage,
+    attempts: i64,
+    next_attempt_at: i64,
+    last_error: &str,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    query(
+        "UPDATE outbox SET attempts = ?1, next_attempt_at = ?2, last_error = ?3, tx_hash = NULL WHERE account_id = ?4 AND local_id = ?5",
+    )
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(last_error)
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    // a broadcast that timed out may still have gone through
+    query(
+        "UPDATE Message SET hash = ?1, transfer_index = ?2 WHERE account_id = ?3 AND local_id = ?4",
+    )
+    .bind(message.hash.as_str())
+    .bind(message.transfer_index)
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Removes a message from the outbox
+pub async fn db_remove_outbox(
+    db: &SqlitePool,
+    account_id: i64,
+    local_id: &str,
+) -> Result<(), Error> {
+    query("DELETE FROM outbox WHERE account_id = ?1 AND local_id = ?2")
+        .bind(account_id)
+        .bind(local_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Stores the broadcast transaction of an outbox message and takes it out of the outbox
+pub async fn db_outbox_sent(
+    db: &SqlitePool,
+    account_id: i64,
+    message: &DbMessage,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    query(&status_update(
+        message.status,
+        ", hash = ?2, fee = ?3, dev_fee = ?4, transfer_index = ?5",
+        "account_id = ?6 AND local_id = ?7",
+    ))
+    .bind(message.status)
+    .bind(message.hash.as_str())
+    .bind(message.fee)
+    .bind(message.dev_fee)
+    .bind(message.transfer_index)
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    query("DELETE FROM outbox WHERE account_id = ?1 AND local_id = ?2")
+        .bind(account_id)
+        .bind(message.local_id.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+/// Fails an outbox message for good, it can still be retried by hand.
+/// The last transaction tried is kept, a retry checks it first.
+pub async fn db_outbox_failed(
+    db: &SqlitePool,
+    account_id: i64,
+    message: &DbMessage,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    query(&status_update(
+        MessageStatus::Failed,
+        ", hash = ?2, transfer_index = ?3",
+        "account_id = ?4 AND local_id = ?5",
+    ))
+    .bind(MessageStatus::Failed)
+    .bind(message.hash.as_str())
+    .bind(message.transfer_index)
+    .bind(account_id)
+    .bind(message.local_id.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    query("DELETE FROM outbox WHERE account_id = ?1 AND local_id = ?2")
+        .bind(account_id)
+        .bind(message.local_id.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    info!("Message {} failed after its last attempt", message.local_id);
+    Ok(())
+}
+
+/// Resets the messages confirmed above the rescan start: incoming ones are orphaned until
+/// the rescan stores them again, outgoing ones wait for their confirmation again. Returns the outgoing hashes.
+pub async fn db_rescan_messages(
+    db: &SqlitePool,
+    account_id: i64,
+    start_topoheight: i64,
+) -> Result<Vec<String>, Error> {
+    let mut tx = db.begin().await?;
+
+    query(&status_update(
+        MessageStatus::Orphaned,
+        "",
+        "account_id = ?2 AND direction = 'Incoming' AND status IN ('Confirmed', 'Final') AND topoheight > ?3",
+    ))
+    .bind(MessageStatus::Orphaned)
+    .bind(account_id)
+    .bind(start_topoheight)
+    .execute(&mut *tx)
+    .await?;
+
+    let reset = status_update(
+        MessageStatus::Broadcast,
+        "",
+        "account_id = ?2 AND direction = 'Outgoing' AND status IN ('Confirmed', 'Final') AND topoheight > ?3",
+    );
+    let outgoing: Vec<(String,)> = query_as(&format!("{reset} RETURNING hash"))
+        .bind(MessageStatus::Broadcast)
+        .bind(account_id)
+        .bind(start_topoheight)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    info!("Messages above topoheight {start_topoheight} reset for the rescan");
+    Ok(outgoing.into_iter().map(|(hash,)| hash).collect())
+}
+
+/// Marks the confirmed messages deep enough below the topoheight as final
+pub async fn db_finalize_messages(
+    db: &SqlitePool,
+    account_id: i64,
+    topoheight: i64,
+) -> Result<u64, Error> {
+    let result = query(&status_update(
+        MessageStatus::Final,
+        "",
+        "account_id = ?2 AND status = 'Confirmed' AND topoheight <= ?3",
+    ))
+    .bind(MessageStatus::Final)
+    .bind(account_id)
+    .bind(topoheight - FINAL_CONFIRMATIONS)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Marks the messages built before the given time that were never broadcast as failed
+pub async fn db_fail_unbroadcast_messages(
+    db: &SqlitePool,
+    account_id: i64,
+    built_before: i64,
+) -> Result<u64, Error> {
+    let result = query(&status_update(
+        MessageStatus::Failed,
+        "",
+        "account_id = ?2 AND direction = 'Outgoing' AND status = 'Built' AND timestamp < ?3",
+    ))
+    .bind(MessageStatus::Failed)
+    .bind(account_id)
+    .bind(built_before)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Stores messages rebuilt from the wallet history, skipping transactions already in the db
+pub async fn db_import_messages(
+    db: &SqlitePool,
+    account_id: i64,
+    messages: Vec<DbMessage>,
+    import_progress: &mut Signal<String>,
+) -> Result<usize> {
+    let known_hashes: Vec<(String,)> =
+        query_as("SELECT DISTINCT hash FROM Message WHERE account_id = ?1 AND hash IS NOT NULL")
+            .bind(account_id)
+            .fetch_all(db)
+            .await?;
+    let known_hashes: HashSet<String> = known_hashes.into_iter().map(|(hash,)| hash).collect();
+
+    let new_messages: Vec<DbMessage> = messages
+        .into_iter()
+        .filter(|message| !known_hashes.contains(&message.hash))
+        .collect();
+    let total = new_messages.len();
+
+    for (i, message) in new_messages.into_iter().enumerate() {
+        import_progress.set(format!("Importing history {}/{total}", i + 1));
+
+        query("INSERT OR IGNORE INTO contacts (name, address, account_id) VALUES (?1, ?1, ?2)")
+            .bind(message.address.as_str())
+            .bind(account_id)
+            .execute(db)
+            .await?;
+
+        db_store_msg(db, Some(account_id), message).await;
+    }
+
+    query("UPDATE user SET history_imported = 1 WHERE id = ?1")
+        .bind(account_id)
+        .execute(db)
+        .await?;
+
+    info!("{total} messages imported from the wallet history");
+    Ok(total)
+}
+
+/// Makes sure the built-in nodes of the network are in the registry
+async fn db_insert_default_nodes(db: &SqlitePool, network: WalletNetwork) -> Result<(), Error> {
+    for endpoint in network.default_nodes() {
+        query("INSERT OR IGNORE INTO nodes (endpoint, network, is_default) VALUES (?1, ?2, 1)")
+            .bind(*endpoint)
+            .bind(network.as_str())
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Reads the node registry of a network, built-in nodes first
+pub async fn db_read_nodes(db: &SqlitePool, network: WalletNetwork) -> Vec<DbNode> {
+    if let Err(e) = db_insert_default_nodes(db, network).await {
+        info!("Cannot add default nodes: {e}");
+    }
+
+    let db_nodes: Result<Vec<DbNode>, Error> = query_as(
+        "SELECT endpoint, network, is_default FROM nodes WHERE network = ?1 ORDER BY is_default DESC, rowid",
+    )
+    .bind(network.as_str())
+    .fetch_all(db)
+    .await;
+
+    match db_nodes {
+        Ok(db_nodes) => db_nodes,
+        Err(e) => {
+            info!("DbNodes retrived with error {e}");
+            Vec::new()
+        }
+    }
+}
+
+#[allow(clippy::redundant_closure)]
+pub async fn db_add_node(db: &SqlitePool, node_ret_msg: &mut Signal<String>, new_node: DbNode) {
+    let network = match new_node.network.parse::<WalletNetwork>() {
+        Ok(network) => network,
+        Err(e) => {
+            node_ret_msg.set(e.to_string());
+            return;
+        }
+    };
+
+    match query("INSERT OR IGNORE INTO nodes (endpoint, network, is_default) VALUES (?1, ?2, 0)")
+        .bind(new_node.endpoint.as_str())
+        .bind(network.as_str())
+        .execute(db)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            info!("Node already exists");
+            node_ret_msg.set("Node already exists".to_string());
+        }
+        Ok(_) => {
+            info!("Node successfully added");
+            node_ret_msg.set("Node successfully added".to_string());
+        }
+        Err(e) => {
+            info!("Error adding node to db: {e}");
+            node_ret_msg.set(format!("Error adding node to db: {}", e).to_string());
+        }
+    }
+}
+
+/// Removes a user added node, built-in nodes are kept
+pub async fn db_remove_node(db: &SqlitePool, endpoint: String, network: WalletNetwork) {
+    match query("DELETE FROM nodes WHERE endpoint = ?1 AND network = ?2 AND is_default = 0")
+        .bind(endpoint)
+        .bind(network.as_str())
+        .execute(db)
+        .await
+    {
+        Ok(_) => info!("Node removed successfully"),
+        Err(e) => info!("{}", e),
+    }
+}
+
+/// Reads the app settings, defaults are used when none are stored yet
+pub async fn db_read_settings(db: &SqlitePool) -> AppSettings {
+    let db_settings: Result<Option<(String,)>, Error> =
+        query_as("SELECT data FROM settings WHERE id = 0")
+            .fetch_optional(db)
+            .await;
+
+    match db_settings {
+        Ok(Some((data,))) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            info!("Stored settings are invalid, using defaults: {e}");
+            AppSettings::default()
+        }),
+        Ok(None) => AppSettings::default(),
+        Err(e) => {
+            info!("Settings retrived with error {e}");
+            AppSettings::default()
+        }
+    }
+}
+
+/// Stores the app settings
+pub async fn db_save_settings(db: &SqlitePool, settings: &AppSettings) -> Result<()> {
+    let data = serde_json::to_string(settings)?;
+
+    query("INSERT INTO settings (id, data) VALUES (0, ?1) ON CONFLICT(id) DO UPDATE SET data = excluded.data")
+        .bind(data)
+        .execute(db)
+        .await?;
+
+    info!("Settings saved");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::views::{MessageDirection, MessageStatus};
+
+    // one connection, an in-memory database lives as long as its connection
+    async fn memory_db() -> SqlitePool {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        run_migrations(&db).await.unwrap();
+        query("INSERT INTO user (username, label) VALUES ('test', 'test')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        db
+    }
+
+    fn message(
+        local_id: &str,
+        direction: MessageDirection,
+        status: MessageStatus,
+        topoheight: i64,
+    ) -> DbMessage {
+        DbMessage {
+            local_id: local_id.to_string(),
+            status,
+            direction,
+            address: "contact".to_string(),
+            hash: format!("hash-{local_id}"),
+            topoheight,
+            ..Default::default()
+        }
+    }
+
+    fn incoming(local_id: &str, status: MessageStatus, topoheight: i64) -> DbMessage {
+        message(local_id, MessageDirection::Incoming, status, topoheight)
+    }
+
+    fn outgoing(local_id: &str, status: MessageStatus, topoheight: i64) -> DbMessage {
+        message(local_id, MessageDirection::Outgoing, status, topoheight)
+    }
+
+    async fn status_of(db: &SqlitePool, local_id: &str) -> MessageStatus {
+        let (status,): (MessageStatus,) =
+            query_as("SELECT status FROM Message WHERE local_id = ?1")
+                .bind(local_id)
+                .fetch_one(db)
+                .await
+                .unwrap();
+        status
+    }
+
+    #[tokio::test]
+    async fn rescan_resets_the_messages_above_its_start() {
+        let db = memory_db().await;
+
+        for message in [
+            incoming("below", MessageStatus::Final, 5),
+            incoming("received", MessageStatus::Confirmed, 20),
+            incoming("final", MessageStatus::Final, 30),
+            outgoing("sent", MessageStatus::Confirmed, 25),
+            outgoing("broadcast", MessageStatus::Broadcast, 40),
+            outgoing("failed", MessageStatus::Failed, 40),
+        ] {
+            db_store_msg(&db, Some(1), message).await;
+        }
+
+        let hashes = db_rescan_messages(&db, 1, 10).await.unwrap();
+        assert_eq!(hashes, vec!["hash-sent".to_string()]);
+
+        assert_eq!(status_of(&db, "below").await, MessageStatus::Final);
+        assert_eq!(status_of(&db, "received").await, MessageStatus::Orphaned);
+        assert_eq!(status_of(&db, "final").await, MessageStatus::Orphaned);
+        assert_eq!(status_of(&db, "sent").await, MessageStatus::Broadcast);
+        assert_eq!(status_of(&db, "broadcast").await, MessageStatus::Broadcast);
+        assert_eq!(status_of(&db, "failed").await, MessageStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn only_messages_built_before_the_session_fail() {
+        let db = memory_db().await;
+
+        for (local_id, timestamp) in [("last-session", 100), ("this-session", 200)] {
+            let built = DbMessage {
+                hash: String::new(),
+                timestamp,
+                ..outgoing(local_id, MessageStatus::Built, 0)
+            };
+            db_store_msg(&db, Some(1), built).await;
+        }
+
+        assert_eq!(db_fail_unbroadcast_messages(&db, 1, 200).await.unwrap(), 1);
+        assert_eq!(status_of(&db, "last-session").await, MessageStatus::Failed);
+        assert_eq!(status_of(&db, "this-session").await, MessageStatus::Built);
+    }
+
+    #[tokio::test]
+    async fn rescanned_messages_are_confirmed_again() {
+        let db = memory_db().await;
+
+        let received = incoming("received", MessageStatus::Confirmed, 20);
+        db_store_msg(&db, Some(1), received.clone()).await;
+        db_rescan_messages(&db, 1, 10).await.unwrap();
+
+        // the rescan stores the same transfer again under a new local id
+        let replayed = DbMessage {
+            local_id: DbMessage::new_local_id(),
+            ..received
+        };
+        db_store_msg(&db, Some(1), replayed).await;
+
+        let (count,): (i64,) = query_as("SELECT COUNT(*) FROM Message")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(status_of(&db, "received").await, MessageStatus::Confirmed);
+    }
+
+    #[tokio::test]
+    async fn status_updates_follow_the_lifecycle() {
+        let db = memory_db().await;
+
+        db_store_msg(&db, Some(1), outgoing("final", MessageStatus::Final, 5)).await;
+        let confirmed = outgoing("confirmed", MessageStatus::Confirmed, 5);
+        db_store_msg(&db, Some(1), confirmed.clone()).await;
+
+        // a message that went through is not failed nor queued again
+        let failed = DbMessage {
+            status: MessageStatus::Failed,
+            ..outgoing("final", MessageStatus::Final, 5)
+        };
+        db_outbox_failed(&db, 1, &failed).await.unwrap();
+        assert_eq!(status_of(&db, "final").await, MessageStatus::Final);
+
+        let queued = db_enqueue_outbox(&db, 1, &confirmed, 0, None, None).await;
+        assert!(matches!(queued, Err(Error::RowNotFound)));
+        assert_eq!(status_of(&db, "confirmed").await, MessageStatus::Confirmed);
+        let due = db_read_due_outbox(&db, 1, i64::MAX).await.unwrap();
+        assert!(due.is_empty());
+
+        // nor replayed back to an earlier status
+        let replayed = DbMessage {
+            status: MessageStatus::Built,
+            ..confirmed
+        };
+        db_store_msg(&db, Some(1), replayed).await;
+        assert_eq!(status_of(&db, "confirmed").await, MessageStatus::Confirmed);
+    }
+
+    #[tokio::test]
+    async fn messages_of_a_window_share_its_deadline() {
+        let db = memory_db().await;
+        assert_eq!(db_outbox_open_window(&db, 1, 100).await.unwrap(), None);
+
+        let first = outgoing("first", MessageStatus::Queued, 0);
+        db_store_msg(&db, Some(1), first.clone()).await;
+        db_enqueue_outbox(&db, 1, &first, 130, None, None)
+            .await
+            .unwrap();
+        assert_eq!(db_outbox_open_window(&db, 1, 110).await.unwrap(), Some(130));
+
+        // a retry waiting for its backoff does not hold the window
+        let retried = outgoing("retried", MessageStatus::Queued, 0);
+        db_store_msg(&db, Some(1), retried.clone()).await;
+        db_enqueue_outbox(&db, 1, &retried, 120, None, None)
+            .await
+            .unwrap();
+        db_outbox_retry_later(&db, 1, &retried, 1, 120, "timeout")
+            .await
+            .unwrap();
+        assert_eq!(db_outbox_open_window(&db, 1, 110).await.unwrap(), Some(130));
+
+        // closed once its deadline passed
+        assert_eq!(db_outbox_open_window(&db, 1, 130).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_keep_their_transaction() {
+        let db = memory_db().await;
+
+        let queued = DbMessage {
+            hash: String::new(),
+            ..outgoing("queued", MessageStatus::Built, 0)
+        };
+        db_store_msg(&db, Some(1), queued.clone()).await;
+        db_enqueue_outbox(&db, 1, &queued, 0, None, None)
+            .await
+            .unwrap();
+
+        let tried = DbMessage {
+            hash: "attempt".to_string(),
+            transfer_index: 2,
+            ..queued
+        };
+        db_outbox_retry_later(&db, 1, &tried, 1, 60, "timeout")
+            .await
+            .unwrap();
+        db_outbox_failed(&db, 1, &tried).await.unwrap();
+
+        let stored = db_read_message(&db, 1, "queued").await.unwrap();
+        assert_eq!(stored.status, MessageStatus::Failed);
+        assert_eq!(stored.hash, "attempt");
+        assert_eq!(stored.transfer_index, 2);
+
+        let due = db_read_due_outbox(&db, 1, i64::MAX).await.unwrap();
+        assert!(due.is_empty());
+    }
+}
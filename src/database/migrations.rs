@@ -0,0 +1,588 @@
+use dioxus::logger::tracing::info;
+use sqlx::{query, query_as, Error, SqliteConnection, SqlitePool};
+
+// A single schema change
+pub enum Step {
+    Sql(&'static str),
+    // adds a column unless it is already there
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+}
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// Ordered schema migrations, the version is stored in `PRAGMA user_version`.
+/// Never edit an applied migration, append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base schema",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS user ( username TEXT NOT NULL, password TEXT NOT NULL )"),
+            Step::Sql("CREATE TABLE IF NOT EXISTS contacts ( name TEXT NOT NULL, address TEXT NOT NULL )"),
+            Step::Sql(
+                "CREATE TABLE IF NOT EXISTS
+                 Message (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     status TEXT NOT NULL,
+                     direction TEXT NOT NULL,
+                     address TEXT NOT NULL,
+                     hash TEXT,
+                     fee REAL,
+                     timestamp INTEGER NOT NULL,
+                     topoheight INTEGER NOT NULL,
+                     asset TEXT NOT NULL,
+                     amount INTEGER NOT NULL,
+                     message TEXT
+                 )",
+            ),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "wallet network, node registry and settings",
+        steps: &[
+            Step::AddColumn {
+                table: "user",
+                column: "network",
+                definition: "TEXT NOT NULL DEFAULT 'testnet'",
+            },
+            Step::Sql(
+                "CREATE TABLE IF NOT EXISTS nodes ( endpoint TEXT NOT NULL, network TEXT NOT NULL, is_default INTEGER NOT NULL DEFAULT 0, UNIQUE(endpoint, network) )",
+            ),
+            Step::Sql(
+                "CREATE TABLE IF NOT EXISTS settings ( id INTEGER PRIMARY KEY CHECK (id = 0), data TEXT NOT NULL )",
+            ),
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "unique contacts and message indexes",
+        steps: &[
+            // older versions could store the same contact several times
+            Step::Sql(
+                "DELETE FROM contacts WHERE rowid NOT IN ( SELECT MIN(rowid) FROM contacts GROUP BY address )",
+            ),
+            Step::Sql("CREATE UNIQUE INDEX IF NOT EXISTS contacts_address ON contacts (address)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_address ON Message (address)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_hash ON Message (hash)"),
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "drop plaintext wallet passwords",
+        steps: &[
+            // the verifier is filled in on the next successful unlock
+            Step::Sql(
+                "CREATE TABLE user_new ( username TEXT NOT NULL, password_hash TEXT, network TEXT NOT NULL DEFAULT 'testnet' )",
+            ),
+            Step::Sql("INSERT INTO user_new (username, network) SELECT username, network FROM user"),
+            Step::Sql("DROP TABLE user"),
+            Step::Sql("ALTER TABLE user_new RENAME TO user"),
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "multiple accounts",
+        steps: &[
+            Step::Sql(
+                "CREATE TABLE user_new ( id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT NOT NULL UNIQUE, label TEXT NOT NULL, password_hash TEXT, network TEXT NOT NULL DEFAULT 'testnet' )",
+            ),
+            Step::Sql(
+                "INSERT OR IGNORE INTO user_new (username, label, password_hash, network) SELECT username, username, password_hash, network FROM user ORDER BY rowid",
+            ),
+            Step::Sql("DROP TABLE user"),
+            Step::Sql("ALTER TABLE user_new RENAME TO user"),
+            Step::AddColumn {
+                table: "contacts",
+                column: "account_id",
+                definition: "INTEGER REFERENCES user (id)",
+            },
+            Step::AddColumn {
+                table: "Message",
+                column: "account_id",
+                definition: "INTEGER REFERENCES user (id)",
+            },
+            // the history so far belongs to the only account that could be opened
+            Step::Sql("UPDATE contacts SET account_id = ( SELECT MIN(id) FROM user ) WHERE account_id IS NULL"),
+            Step::Sql("UPDATE Message SET account_id = ( SELECT MIN(id) FROM user ) WHERE account_id IS NULL"),
+            Step::Sql("DROP INDEX IF EXISTS contacts_address"),
+            Step::Sql("CREATE UNIQUE INDEX IF NOT EXISTS contacts_account_address ON contacts (account_id, address)"),
+            Step::Sql("DROP INDEX IF EXISTS message_address"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_account_address ON Message (account_id, address)"),
+        ],
+    },
+    Migration {
+        version: 6,
+        description: "history import flag",
+        steps: &[
+            // existing accounts already built their history from live events
+            Step::AddColumn {
+                table: "user",
+                column: "history_imported",
+                definition: "INTEGER NOT NULL DEFAULT 1",
+            },
+        ],
+    },
+    Migration {
+        version: 7,
+        description: "message ids and replies",
+        steps: &[
+            Step::AddColumn {
+                table: "Message",
+                column: "message_id",
+                definition: "TEXT",
+            },
+            Step::AddColumn {
+                table: "Message",
+                column: "reply_to",
+                definition: "TEXT",
+            },
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_account_message_id ON Message (account_id, message_id)"),
+        ],
+    },
+    Migration {
+        version: 8,
+        description: "message kinds and payment requests",
+        steps: &[
+            Step::AddColumn {
+                table: "Message",
+                column: "kind",
+                definition: "TEXT NOT NULL DEFAULT 'text'",
+            },
+            Step::AddColumn {
+                table: "Message",
+                column: "request_amount",
+                definition: "INTEGER",
+            },
+            Step::AddColumn {
+                table: "Message",
+                column: "request_asset",
+                definition: "TEXT",
+            },
+            Step::AddColumn {
+                table: "Message",
+                column: "request_expires_at",
+                definition: "INTEGER",
+            },
+        ],
+    },
+    Migration {
+        version: 9,
+        description: "dev fee per message",
+        steps: &[
+            // dev fees paid before this version were not recorded
+            Step::AddColumn {
+                table: "Message",
+                column: "dev_fee",
+                definition: "REAL NOT NULL DEFAULT 0",
+            },
+        ],
+    },
+    Migration {
+        version: 10,
+        description: "message outbox",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS
+             outbox (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 account_id INTEGER NOT NULL REFERENCES user(id),
+                 message_id TEXT NOT NULL,
+                 attempts INTEGER NOT NULL DEFAULT 0,
+                 next_attempt_at INTEGER NOT NULL,
+                 tx_hash TEXT,
+                 last_error TEXT,
+                 UNIQUE(account_id, message_id)
+             )",
+        )],
+    },
+    Migration {
+        version: 11,
+        description: "transfer index per message",
+        steps: &[
+            Step::AddColumn {
+                table: "Message",
+                column: "transfer_index",
+                definition: "INTEGER NOT NULL DEFAULT 0",
+            },
+            // rows sharing a hash were batched or stored twice, they are kept apart in insertion order
+            Step::Sql(
+                "UPDATE Message SET transfer_index = (
+                     SELECT COUNT(*) FROM Message AS previous
+                     WHERE previous.account_id IS Message.account_id AND previous.hash = Message.hash AND previous.id < Message.id
+                 )
+                 WHERE hash IS NOT NULL AND hash != ''",
+            ),
+            Step::Sql(
+                "CREATE UNIQUE INDEX IF NOT EXISTS message_account_hash_transfer ON Message (account_id, hash, transfer_index) WHERE hash IS NOT NULL AND hash != ''",
+            ),
+        ],
+    },
+    Migration {
+        version: 12,
+        description: "local message id",
+        steps: &[
+            Step::AddColumn {
+                table: "Message",
+                column: "local_id",
+                definition: "TEXT",
+            },
+            Step::Sql(
+                "UPDATE Message SET local_id = lower(hex(randomblob(16))) WHERE local_id IS NULL",
+            ),
+            Step::Sql("CREATE UNIQUE INDEX IF NOT EXISTS message_local_id ON Message (local_id)"),
+        ],
+    },
+    Migration {
+        version: 13,
+        description: "typed message status and direction",
+        steps: &[
+            // a CHECK constraint cannot be added to a column, the table is rebuilt
+            Step::Sql(
+                "CREATE TABLE Message_new (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     status TEXT NOT NULL CHECK (status IN ('Draft', 'Queued', 'Built', 'Broadcast', 'InMempool', 'Confirmed', 'Final', 'Failed', 'Orphaned')),
+                     direction TEXT NOT NULL CHECK (direction IN ('Incoming', 'Outgoing')),
+                     address TEXT NOT NULL,
+                     hash TEXT,
+                     fee REAL,
+                     timestamp INTEGER NOT NULL,
+                     topoheight INTEGER NOT NULL,
+                     asset TEXT NOT NULL,
+                     amount INTEGER NOT NULL,
+                     message TEXT,
+                     account_id INTEGER REFERENCES user (id),
+                     message_id TEXT,
+                     reply_to TEXT,
+                     kind TEXT NOT NULL DEFAULT 'text',
+                     request_amount INTEGER,
+                     request_asset TEXT,
+                     request_expires_at INTEGER,
+                     dev_fee REAL NOT NULL DEFAULT 0,
+                     transfer_index INTEGER NOT NULL DEFAULT 0,
+                     local_id TEXT NOT NULL
+                 )",
+            ),
+            // confirmed messages become final on the next topoheight
+            Step::Sql(
+                "INSERT INTO Message_new (id, status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message, account_id, message_id, reply_to, kind, request_amount, request_asset, request_expires_at, dev_fee, transfer_index, local_id)
+                 SELECT
+                     id,
+                     CASE
+                         WHEN status = 'Pending' THEN 'Built'
+                         WHEN status = 'Sent' THEN 'Broadcast'
+                         WHEN status = 'Received' THEN 'Confirmed'
+                         WHEN status IN ('Queued', 'Failed', 'Orphaned') THEN status
+                         ELSE 'Failed'
+                     END,
+                     CASE WHEN direction = 'Outgoing' THEN 'Outgoing' ELSE 'Incoming' END,
+                     address, hash, fee, timestamp, topoheight, asset, amount, message, account_id, message_id, reply_to, kind, request_amount, request_asset, request_expires_at, dev_fee, transfer_index,
+                     COALESCE(local_id, lower(hex(randomblob(16))))
+                 FROM Message",
+            ),
+            Step::Sql("DROP TABLE Message"),
+            Step::Sql("ALTER TABLE Message_new RENAME TO Message"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_hash ON Message (hash)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_account_address ON Message (account_id, address)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS message_account_message_id ON Message (account_id, message_id)"),
+            Step::Sql(
+                "CREATE UNIQUE INDEX IF NOT EXISTS message_account_hash_transfer ON Message (account_id, hash, transfer_index) WHERE hash IS NOT NULL AND hash != ''",
+            ),
+            Step::Sql("CREATE UNIQUE INDEX IF NOT EXISTS message_local_id ON Message (local_id)"),
+        ],
+    },
+    Migration {
+        version: 14,
+        description: "scrub the pages freed by the plaintext passwords",
+        // nothing to change, `run_migrations` vacuums the database once it reaches this version
+        steps: &[],
+    },
+    Migration {
+        version: 15,
+        description: "outbox keyed on the local message id",
+        steps: &[
+            // the envelope id is picked by the sender, only outgoing messages can be queued
+            Step::Sql(
+                "CREATE TABLE outbox_new (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     account_id INTEGER NOT NULL REFERENCES user(id),
+                     local_id TEXT NOT NULL,
+                     attempts INTEGER NOT NULL DEFAULT 0,
+                     next_attempt_at INTEGER NOT NULL,
+                     tx_hash TEXT,
+                     last_error TEXT,
+                     UNIQUE(account_id, local_id)
+                 )",
+            ),
+            Step::Sql(
+                "INSERT OR IGNORE INTO outbox_new (id, account_id, local_id, attempts, next_attempt_at, tx_hash, last_error)
+                 SELECT outbox.id, outbox.account_id, Message.local_id, outbox.attempts, outbox.next_attempt_at, outbox.tx_hash, outbox.last_error
+                 FROM outbox
+                 JOIN Message ON Message.account_id = outbox.account_id AND Message.message_id = outbox.message_id AND Message.direction = 'Outgoing'",
+            ),
+            Step::Sql("DROP TABLE outbox"),
+            Step::Sql("ALTER TABLE outbox_new RENAME TO outbox"),
+        ],
+    },
+];
+
+// Version after which the database is vacuumed once: migration 4 dropped the plaintext
+// passwords but their content stays in the free pages until they are rewritten
+const SCRUB_VERSION: i64 = 14;
+
+/// Reads the schema version of the database
+pub async fn schema_version(db: &SqlitePool) -> Result<i64, Error> {
+    let (version,): (i64,) = query_as("PRAGMA user_version").fetch_one(db).await?;
+    Ok(version)
+}
+
+async fn has_column(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool, Error> {
+    let found: Option<(String,)> =
+        query_as("SELECT name FROM pragma_table_info(?1) WHERE name = ?2")
+            .bind(table)
+            .bind(column)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    Ok(found.is_some())
+}
+
+async fn apply_step(conn: &mut SqliteConnection, step: &Step) -> Result<(), Error> {
+    match step {
+        Step::Sql(sql) => {
+            query(sql).execute(&mut *conn).await?;
+        }
+        Step::AddColumn {
+            table,
+            column,
+            definition,
+        } => {
+            if !has_column(conn, table, column).await? {
+                query(&format!(
+                    "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies every migration newer than the database schema version, each one in its own transaction
+pub async fn run_migrations(db: &SqlitePool) -> Result<i64, Error> {
+    let start_version = schema_version(db).await?;
+    let mut version = start_version;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        let mut tx = db.begin().await?;
+
+        for step in migration.steps {
+            apply_step(&mut tx, step).await?;
+        }
+
+        // pragmas cannot be bound, the version is a trusted constant
+        query(&format!("PRAGMA user_version = {}", migration.version))
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        info!(
+            "Database migrated to version {}: {}",
+            migration.version, migration.description
+        );
+        version = migration.version;
+    }
+
+    // vacuum cannot run in a transaction
+    if start_version < SCRUB_VERSION && version >= SCRUB_VERSION {
+        query("VACUUM").execute(db).await?;
+        info!("Database vacuumed");
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    // tables and rows as left by the versions before the migrations
+    const BASELINE: &[&str] = &[
+        "CREATE TABLE IF NOT EXISTS user ( username TEXT NOT NULL, password TEXT NOT NULL )",
+        "CREATE TABLE IF NOT EXISTS contacts ( name TEXT NOT NULL, address TEXT NOT NULL )",
+        "CREATE TABLE IF NOT EXISTS
+         Message (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             status TEXT NOT NULL,
+             direction TEXT NOT NULL,
+             address TEXT NOT NULL,
+             hash TEXT,
+             fee REAL,
+             timestamp INTEGER NOT NULL,
+             topoheight INTEGER NOT NULL,
+             asset TEXT NOT NULL,
+             amount INTEGER NOT NULL,
+             message TEXT
+         )",
+        "INSERT INTO user (username, password) VALUES ('alice', 'hunter2')",
+        // the same contact could be stored twice
+        "INSERT INTO contacts (name, address) VALUES ('bob', 'xet:bob'), ('bob', 'xet:bob'), ('carol', 'xet:carol')",
+        "INSERT INTO Message (status, direction, address, hash, fee, timestamp, topoheight, asset, amount, message) VALUES
+             ('Sent', 'Outgoing', 'xet:bob', 'aa', 0.0001, 1, 10, 'xel', 0, 'hello bob'),
+             ('Received', 'Incoming', 'xet:bob', 'bb', NULL, 2, 11, 'xel', 0, 'hello alice'),
+             ('Pending', 'Outgoing', 'xet:carol', 'cc', 0.0001, 3, 12, 'xel', 0, 'hello carol'),
+             ('Received', 'Incoming', 'xet:carol', 'dd', NULL, 4, 13, 'xel', 0, 'first'),
+             ('Received', 'Incoming', 'xet:carol', 'dd', NULL, 4, 13, 'xel', 0, 'second')",
+    ];
+
+    // one connection, an in-memory database lives as long as its connection
+    async fn memory_db() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    async fn baseline_db() -> SqlitePool {
+        let db = memory_db().await;
+        for sql in BASELINE {
+            query(sql).execute(&db).await.unwrap();
+        }
+        db
+    }
+
+    async fn columns(db: &SqlitePool, table: &str) -> Vec<String> {
+        let columns: Vec<(String,)> = query_as("SELECT name FROM pragma_table_info(?1)")
+            .bind(table)
+            .fetch_all(db)
+            .await
+            .unwrap();
+        columns.into_iter().map(|(name,)| name).collect()
+    }
+
+    fn latest_version() -> i64 {
+        MIGRATIONS.last().unwrap().version
+    }
+
+    #[test]
+    fn versions_follow_each_other() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "{}", migration.description);
+        }
+    }
+
+    #[tokio::test]
+    async fn new_database_gets_the_latest_schema() {
+        let db = memory_db().await;
+
+        assert_eq!(run_migrations(&db).await.unwrap(), latest_version());
+        assert_eq!(schema_version(&db).await.unwrap(), latest_version());
+
+        for table in ["user", "contacts", "Message", "nodes", "settings", "outbox"] {
+            assert!(!columns(&db, table).await.is_empty(), "{table}");
+        }
+    }
+
+    #[tokio::test]
+    async fn baseline_database_is_upgraded() {
+        let db = baseline_db().await;
+
+        assert_eq!(run_migrations(&db).await.unwrap(), latest_version());
+        assert_eq!(schema_version(&db).await.unwrap(), latest_version());
+
+        // the plaintext password is gone, the account is kept
+        assert!(!columns(&db, "user").await.contains(&"password".to_string()));
+        let users: Vec<(i64, String, String, Option<String>, String, bool)> = query_as(
+            "SELECT id, username, label, password_hash, network, history_imported FROM user",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(
+            users,
+            vec![(
+                1,
+                "alice".to_string(),
+                "alice".to_string(),
+                None,
+                "testnet".to_string(),
+                true
+            )]
+        );
+
+        let contacts: Vec<(String, Option<i64>)> =
+            query_as("SELECT address, account_id FROM contacts ORDER BY address")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(
+            contacts,
+            vec![
+                ("xet:bob".to_string(), Some(1)),
+                ("xet:carol".to_string(), Some(1))
+            ]
+        );
+
+        let messages: Vec<(String, String, String, i64, Option<i64>, String)> = query_as(
+            "SELECT status, direction, hash, transfer_index, account_id, message FROM Message ORDER BY id",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let expected = [
+            ("Broadcast", "Outgoing", "aa", 0, "hello bob"),
+            ("Confirmed", "Incoming", "bb", 0, "hello alice"),
+            ("Built", "Outgoing", "cc", 0, "hello carol"),
+            ("Confirmed", "Incoming", "dd", 0, "first"),
+            ("Confirmed", "Incoming", "dd", 1, "second"),
+        ]
+        .map(|(status, direction, hash, transfer_index, message)| {
+            (
+                status.to_string(),
+                direction.to_string(),
+                hash.to_string(),
+                transfer_index,
+                Some(1),
+                message.to_string(),
+            )
+        });
+        assert_eq!(messages, expected);
+
+        // every message got its own local id
+        let (local_ids,): (i64,) =
+            query_as("SELECT COUNT(DISTINCT local_id) FROM Message WHERE local_id != ''")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(local_ids, 5);
+    }
+
+    #[tokio::test]
+    async fn second_run_does_nothing() {
+        let db = baseline_db().await;
+        run_migrations(&db).await.unwrap();
+
+        let snapshot = "SELECT local_id, status, transfer_index FROM Message ORDER BY id";
+        let before: Vec<(String, String, i64)> = query_as(snapshot).fetch_all(&db).await.unwrap();
+
+        assert_eq!(run_migrations(&db).await.unwrap(), latest_version());
+        assert_eq!(schema_version(&db).await.unwrap(), latest_version());
+
+        let after: Vec<(String, String, i64)> = query_as(snapshot).fetch_all(&db).await.unwrap();
+        assert_eq!(before, after);
+    }
+}
@@ -1 +1,2 @@
 pub mod db_fns;
+pub mod migrations;
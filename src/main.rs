@@ -3,20 +3,29 @@
 use dioxus::prelude::*;
 
 use sqlx::SqlitePool;
+use std::time::Instant;
 use tokio::sync::RwLock;
 use views::{
+    accounts::Accounts,
     chat_view::ChatView,
     home::{AddContact, Home, ViewSeed},
+    nodes::Nodes,
+    session::Session,
+    settings::Settings,
     restore_wallet_options::{
         CreateNewWallet, RestoreFromPrivateKey, RestoreFromSeed, RestoreWalletOptions,
     },
     splashscreen::SplashScreen,
+    unlock::Unlock,
+    wallet_view::WalletView,
 };
 
-use crate::wallet::utils::ChatWallet;
+use crate::{settings::AppSettings, wallet::utils::ChatWallet};
 /// Define a components module that contains all shared components for our app.
 mod components;
 mod database;
+/// App settings persisted in the database.
+mod settings;
 /// Define a views module that contains the UI for all Layouts and Routes for our app.
 mod views;
 mod wallet;
@@ -32,6 +41,8 @@ enum Route {
     // many routes with a common UI like a navbar.
     #[route("/")]
     SplashScreen {},
+    #[route("/unlock")]
+    Unlock {},
     #[route("/restorewalletoptions")]
     RestoreWalletOptions {},
     #[route("/createnewwallet")]
@@ -40,14 +51,24 @@ enum Route {
     RestoreFromSeed {},
     #[route("/restorefromprivkey")]
     RestoreFromPrivateKey {},
-    #[route("/home")]
-    Home {},
-    #[route("/chatview?:name&:address")]
-    ChatView { name: String, address: String },
-    #[route("/addcontact")]
-    AddContact {},
-    #[route("/viewseed")]
-    ViewSeed {},
+    // Routes that need an unlocked wallet, the session locks itself when idle
+    #[layout(Session)]
+        #[route("/home")]
+        Home {},
+        #[route("/chatview?:name&:address")]
+        ChatView { name: String, address: String },
+        #[route("/addcontact")]
+        AddContact {},
+        #[route("/viewseed")]
+        ViewSeed {},
+        #[route("/nodes")]
+        Nodes {},
+        #[route("/settings")]
+        Settings {},
+        #[route("/accounts")]
+        Accounts {},
+        #[route("/wallet")]
+        WalletView {},
 }
 
 // We can import assets in dioxus with the `asset!` macro. This macro takes a path to an asset relative to the crate root.
@@ -59,7 +80,10 @@ const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css");
 
 pub static DB: GlobalSignal<Option<SqlitePool>> = Signal::global(|| None);
 pub static WALLET: GlobalSignal<Option<RwLock<ChatWallet>>> = Signal::global(|| None);
-pub static IS_READY: GlobalSignal<RwLock<bool>> = Signal::global(|| RwLock::new(true));
+// Account of the open wallet, kept while locked to preselect it on unlock
+pub static ACCOUNT: GlobalSignal<Option<i64>> = Signal::global(|| None);
+pub static SETTINGS: GlobalSignal<AppSettings> = Signal::global(AppSettings::default);
+pub static LAST_ACTIVITY: GlobalSignal<Instant> = Signal::global(Instant::now);
 
 fn main() {
     // call to fix crypto provider issue
@@ -81,7 +105,7 @@ fn App() -> Element {
     // The `rsx!` macro lets us define HTML inside of rust. It expands to an Element with all of our HTML inside.
     rsx!(
 
-    div { class:"flex flex-col h-screen w-full bg-black",
+    div { class:"flex flex-col h-screen w-full {SETTINGS.read().theme.root_class()}",
         document::Stylesheet{
             href: asset!("/assets/tailwind.css")
         }
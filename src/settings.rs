@@ -0,0 +1,71 @@
+//! App settings, persisted as a single row in the settings table.
+
+use serde::{Deserialize, Serialize};
+use xelis_common::config::XELIS_ASSET;
+
+use crate::wallet::utils::{MnemonicLanguage, TableSize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    /// Classes of the app root, the light theme remaps the colors every view uses
+    pub fn root_class(&self) -> &'static str {
+        match self {
+            Theme::Dark => "bg-black",
+            Theme::Light => "theme-light bg-black",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // preferred node, the node registry is used when empty
+    pub node_endpoint: Option<String>,
+    pub table_size: TableSize,
+    pub n_threads: usize,
+    pub mnemonic_language: MnemonicLanguage,
+    pub theme: Theme,
+    pub dev_fee_enabled: bool,
+    // XEL per message, the network default is used when empty
+    pub dev_fee_amount: Option<f64>,
+    // idle time before the session locks, 0 never locks
+    pub lock_timeout_secs: u64,
+    // assets shown besides XELIS, any other token is hidden as spam
+    pub allowed_assets: Vec<String>,
+    // send the messages typed within the batch window in one transaction
+    pub batch_messages: bool,
+    pub batch_window_secs: u64,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            node_endpoint: None,
+            table_size: TableSize::default(),
+            n_threads: 4,
+            mnemonic_language: MnemonicLanguage::default(),
+            theme: Theme::default(),
+            dev_fee_enabled: true,
+            dev_fee_amount: None,
+            lock_timeout_secs: 300,
+            allowed_assets: Vec::new(),
+            batch_messages: false,
+            batch_window_secs: 10,
+        }
+    }
+}
+
+impl AppSettings {
+    /// XELIS is always shown, other assets only once they are allowed
+    pub fn is_asset_allowed(&self, asset_hash: &str) -> bool {
+        asset_hash == XELIS_ASSET.to_hex() || self.allowed_assets.iter().any(|hash| hash == asset_hash)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    ACCOUNT, DB, Route,
+    database::db_fns::{DbUserLogin, db_read_users, db_remove_user, db_rename_user},
+};
+use dioxus::{logger::tracing::info, prelude::*};
+
+/// Closes the open wallet and asks for the password of another account
+pub fn switch_account(account_id: i64) {
+    let nav = navigator();
+
+    *ACCOUNT.write() = Some(account_id);
+
+    // the unlock screen closes the current wallet before opening the next one
+    nav.push(Route::Unlock {});
+}
+
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+#[component]
+pub fn Accounts() -> Element {
+    let nav = navigator();
+
+    let mut accounts = use_signal(|| Vec::<DbUserLogin>::new());
+    let mut new_label = use_signal(|| String::new());
+    let mut accounts_ret_msg = use_signal(|| String::new());
+    // account whose removal waits for the user to confirm it
+    let mut confirm_remove = use_signal(|| None::<i64>);
+
+    let mut db_accounts = use_resource(move || async move {
+        if let Some(db) = &*DB.read() {
+            match db_read_users(db).await {
+                Ok(db_users) => accounts.set(db_users),
+                Err(e) => info!("Accounts error: {e}"),
+            }
+        }
+    });
+
+    let rename_account = move |_: FormEvent| async move {
+        let label = new_label.read().trim().to_string();
+
+        let Some(account_id) = *ACCOUNT.read() else {
+            return;
+        };
+
+        if label.is_empty() {
+            accounts_ret_msg.set("Name cannot be empty".to_string());
+            return;
+        }
+
+        if let Some(db) = &*DB.read() {
+            match db_rename_user(db, account_id, label).await {
+                Ok(_) => accounts_ret_msg.set("Account renamed".to_string()),
+                Err(e) => accounts_ret_msg.set(e.to_string()),
+            }
+        }
+
+        new_label.set(String::new());
+        db_accounts.restart();
+    };
+
+    let remove_account = move |account_id: i64| async move {
+        confirm_remove.set(None);
+
+        if let Some(db) = &*DB.read() {
+            if let Err(e) = db_remove_user(db, account_id).await {
+                accounts_ret_msg.set(e.to_string());
+                return;
+            }
+        }
+
+        if Some(account_id) == *ACCOUNT.read() {
+            // the open wallet is gone, pick another account
+            *ACCOUNT.write() = None;
+            nav.push(Route::Unlock {});
+        } else {
+            db_accounts.restart();
+        }
+    };
+
+    rsx!(
+        div {
+            class: "outline-2 outline-green-700 rounded-xl mx-4 mt-4",
+            div {
+                class: "container mx-auto",
+                div {
+                    class: "flex justify-between items-center ",
+                    button {
+                        class: "text-xl font-semibold text-green-600 hover:text-green-500 p-4",
+                        id: "go-home",
+                        onclick: move |_| { nav.push(Route::Home {}); },
+                        "<"
+                    }
+                    h1 {
+                        class: "text-xl font-semibold text-green-600",
+                        "Accounts"
+                    }
+                    button {
+                        class: "text-xl text-green-600 hover:text-green-500 p-4",
+                        onclick: move |_| { nav.push(Route::RestoreWalletOptions {}); },
+                        "+"
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex flex-col p-4",
+            for account in accounts.read().iter().cloned() {
+                div {
+                    class: "flex justify-between items-center outline-2 outline-green-700 rounded-xl p-4 mb-4 text-green-600",
+                    div {
+                        p { "{account.label}" }
+                        p { class: "text-sm text-green-900", "{account.username} | {account.network}" }
+                    }
+                    if Some(account.id) == *ACCOUNT.read() {
+                        p { class: "text-sm", "Open" }
+                    } else {
+                        button {
+                            class: "hover:text-green-500",
+                            onclick: move |_| switch_account(account.id),
+                            "Switch"
+                        }
+                    }
+                    button {
+                        class: "hover:text-green-500",
+                        onclick: move |_| confirm_remove.set(Some(account.id)),
+                        "Remove"
+                    }
+                }
+                // the chat history of the account is deleted with it
+                if confirm_remove() == Some(account.id) {
+                    div {
+                        class: "flex justify-between items-center outline-2 outline-green-600 rounded-xl p-4 mb-4 text-green-600",
+                        p { "Remove {account.label} and its whole chat history?" }
+                        div {
+                            class: "flex-none",
+                            button {
+                                class: "outline-2 outline-green-700 rounded-xl px-4 py-2 mr-2 hover:text-green-500",
+                                onclick: move |_| confirm_remove.set(None),
+                                "Keep"
+                            }
+                            button {
+                                class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 hover:text-black px-4 py-2",
+                                onclick: move |_| remove_account(account.id),
+                                "Remove"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex items-center justify-center",
+            form {
+                class: "p-4 w-full max-w-md",
+                onsubmit: rename_account,
+                div {
+                    class: "flex",
+                    input {
+                        id: "account-label",
+                        class: "grow outline-2 outline-green-600 rounded-xl p-4 mb-4 text-green-600",
+                        placeholder: "Rename the open account...",
+                        value: "{new_label}",
+                        oninput: move |event| new_label.set(event.value())
+                    }
+                }
+                div {
+                    class: "flex justify-center",
+                    button {
+                        class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 text-green-600 hover:text-black p-4",
+                        r#type: "submit",
+                        "Rename"
+                    }
+                }
+                div {
+                    class: "text-green-600",
+                    "{accounts_ret_msg.read()}"
+                }
+            }
+        }
+    )
+}
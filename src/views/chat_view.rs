@@ -1,14 +1,31 @@
 use crate::{
-    DB, IS_READY, Route, WALLET,
-    database::db_fns::{
-        db_read_messages, db_remove_contact, db_store_init_message, db_update_status_topoheight,
+    DB, Route, SETTINGS, WALLET,
+    database::db_fns::{db_read_messages, db_remove_contact},
+    views::{DbMessage, FINAL_CONFIRMATIONS, MessageDirection, MessageStatus},
+    wallet::{
+        events::{AppEvent, subscribe_app_events},
+        message::{Envelope, MAX_MESSAGE_PARTS, MESSAGE_PART_SIZE, MessageKind, parts_needed},
+        utils::{AssetInfo, Payment},
+        wallet_fns::{
+            PendingSend, new_message_id, wallet_atomic_amount, wallet_build_message,
+            wallet_cancel_message, wallet_confirm_message, wallet_queue_message,
+            wallet_rebuild_message, wallet_retry_message,
+        },
     },
-    views::DbMessage,
-    wallet::wallet_fns::{DEV_FEE_AMOUNT, wallet_send_message},
 };
 use chrono::Utc;
 use chrono::{self, TimeZone};
 use dioxus::{logger::tracing::info, prelude::*};
+use tokio::sync::broadcast::error::RecvError;
+use xelis_common::utils::format_xelis;
+
+// Validity choices of a payment request
+const REQUEST_EXPIRY_OPTIONS: [(&str, Option<i64>); 4] = [
+    ("never expires", None),
+    ("expires in 1 hour", Some(3600)),
+    ("expires in 1 day", Some(86400)),
+    ("expires in 1 week", Some(604800)),
+];
 
 #[allow(
     clippy::redundant_closure,
@@ -39,37 +56,253 @@ pub fn ChatView(name: String, address: String) -> Element {
             }
         };
 
-        // get the last message fee
+        // get the last message network fee, the dev fee is recorded apart
         if let Some(message) = messages_from_db.read().last() {
-            last_msg_fee.set(message.fee + DEV_FEE_AMOUNT);
+            last_msg_fee.set(message.fee);
         }
     });
 
     // message signal
     let mut send_msg = use_signal(|| String::new());
     let mut info = use_signal(|| String::new());
-    let mut wallet_is_ready = use_signal(|| true);
+    // message the composer answers
+    let mut reply_to = use_signal(|| None::<DbMessage>);
+    // built transactions waiting for the user to confirm their cost, in nonce order
+    let mut pending_sends = use_signal(|| Vec::<PendingSend>::new());
+    // payment attached to the message
+    let mut send_amount = use_signal(|| String::new());
+    let mut asset_hash = use_signal(|| String::new());
+    let mut request_expiry = use_signal(|| 0usize);
+    let mut assets = use_signal(|| Vec::<AssetInfo>::new());
 
-    let subbmit_tx_message = move |_: FormEvent| async move {
-        // only store input msg if it is not empty
-        if !(*send_msg.read()).is_empty() {
-            let message = send_msg.read().clone();
-            send_msg.set("".to_string());
+    // assets known to the wallet, the picker only offers the allowed ones
+    let mut wallet_assets_handle = use_future(move || async move {
+        if let Some(wallet) = &*WALLET.read() {
+            match wallet.read().await.get_assets().await {
+                Ok(wallet_assets) => assets.set(wallet_assets),
+                Err(e) => info!("Assets read error: {e}"),
+            }
+        }
+    });
+
+    // asset of the payment, the first allowed one until the user picks another
+    let picked_asset = move || {
+        let assets = assets.read();
+        let allowed: Vec<&AssetInfo> = assets
+            .iter()
+            .filter(|asset| SETTINGS.read().is_asset_allowed(&asset.hash))
+            .collect();
+        allowed
+            .iter()
+            .find(|asset| asset.hash == *asset_hash.read())
+            .or(allowed.first())
+            .map(|asset| (*asset).clone())
+    };
 
-            *IS_READY.write().write().await = false;
-            wallet_is_ready.set(false);
+    // every part is sent as its own transfer
+    let message_len = use_memo(move || send_msg.read().len());
+    let message_parts = use_memo(move || parts_needed(&send_msg.read()));
 
-            wallet_send_message(
+    // builds the message so the user confirms its cost, or queues it for the next batch
+    let send_envelope = move |envelope: Envelope, payment: Option<Payment>| async move {
+        if SETTINGS.read().batch_messages {
+            wallet_queue_message(
                 contact_address.read().clone(),
                 *topoheight.read(),
-                message,
+                envelope,
+                payment,
                 &mut db_message_handle,
                 &mut info,
             )
             .await;
+        } else {
+            let built = wallet_build_message(
+                contact_address.read().clone(),
+                *topoheight.read(),
+                envelope,
+                payment,
+                &mut info,
+            )
+            .await;
+            if let Some(built) = built {
+                pending_sends.write().push(built);
+            }
+        }
+    };
+
+    let subbmit_tx_message = move |_: FormEvent| async move {
+        let amount = send_amount.read().trim().to_string();
+        let payment = if amount.is_empty() {
+            None
+        } else {
+            match amount.parse::<f64>() {
+                Ok(float_amount) if float_amount > 0.0 => {
+                    let Some(asset) = picked_asset() else {
+                        return;
+                    };
+
+                    match wallet_atomic_amount(float_amount, &asset.hash).await {
+                        Ok(amount) => Some(Payment {
+                            amount,
+                            asset_hash: asset.hash,
+                        }),
+                        Err(e) => {
+                            info.set(e.to_string());
+                            return;
+                        }
+                    }
+                }
+                Ok(_) => None,
+                Err(_) => {
+                    info.set("Invalid amount".to_string());
+                    return;
+                }
+            }
+        };
+
+        // only store input msg if it is not empty or it carries a payment
+        if !(*send_msg.read()).is_empty() || payment.is_some() {
+            let message = send_msg.read().clone();
+            send_msg.set("".to_string());
+            send_amount.set("".to_string());
+
+            let envelope = Envelope {
+                reply_to: reply_to.take().map(|original| original.reference()),
+                ..Envelope::text(&new_message_id(), &message)
+            };
+
+            send_envelope(envelope, payment).await;
+        }
+    };
+
+    // asks the contact for the amount in the composer, the text is the memo
+    let request_payment = move |_| async move {
+        let Ok(float_amount) = send_amount.read().trim().parse::<f64>() else {
+            info.set("Enter the amount to request".to_string());
+            return;
+        };
+        let Some(asset) = picked_asset() else {
+            return;
+        };
+
+        let amount = match wallet_atomic_amount(float_amount, &asset.hash).await {
+            Ok(amount) => amount,
+            Err(e) => {
+                info.set(e.to_string());
+                return;
+            }
+        };
+
+        let expires_at = REQUEST_EXPIRY_OPTIONS[request_expiry()]
+            .1
+            .map(|secs| (Utc::now().timestamp() + secs) as u64);
+
+        let envelope = Envelope::payment_request(
+            &new_message_id(),
+            &send_msg.read(),
+            amount,
+            asset.hash,
+            expires_at,
+        );
+
+        send_msg.set("".to_string());
+        send_amount.set("".to_string());
+
+        send_envelope(envelope, None).await;
+    };
+
+    // pays a request of the contact, the payment replies to it
+    let pay_request = move |request: DbMessage| async move {
+        let asset_hash = request.request_asset.clone().unwrap_or_default();
+        if !assets.read().iter().any(|asset| asset.hash == asset_hash) {
+            info.set("Unknown asset, cannot pay this request".to_string());
+            return;
+        }
+
+        // the requested atomic amount is paid as is
+        let payment = Payment {
+            amount: request.request_amount.unwrap_or_default() as u64,
+            asset_hash,
+        };
+        send_envelope(
+            Envelope::payment(&new_message_id(), request.reference()),
+            Some(payment),
+        )
+        .await;
+    };
+
+    // broadcasts the first built message once the user accepted its cost
+    let confirm_send = move |_| async move {
+        // the next message can be sent while this one confirms,
+        // queued messages are sent by the outbox worker
+        if pending_sends.read().is_empty() {
+            return;
+        }
+
+        let built = pending_sends.write().remove(0);
+        // a stale transaction comes back built again, at a cost to confirm
+        if let Some(rebuilt) =
+            wallet_confirm_message(built, &mut db_message_handle, &mut info).await
+        {
+            pending_sends.write().push(rebuilt);
+        }
+    };
+
+    // queues a failed message again
+    let retry_message = move |db_message: DbMessage| async move {
+        match wallet_retry_message(db_message).await {
+            Ok(true) => {
+                info.set("Message queued again".to_string());
+                db_message_handle.restart();
+            }
+            Ok(false) => {
+                info.set("The message went through after all, it is not sent again".to_string());
+                db_message_handle.restart();
+            }
+            Err(e) => {
+                info!("Retry error: {e}");
+                info.set(format!("Retry error: {e}"));
+            }
+        }
+    };
+
+    let cancel_send = move |index: usize| async move {
+        if index >= pending_sends.read().len() {
+            return;
+        }
+
+        let mut later = pending_sends.write().split_off(index).into_iter();
+        let Some(cancelled) = later.next() else {
+            return;
+        };
+        let discarded = wallet_cancel_message(cancelled).await;
+
+        // the messages built on the cancelled one are built again, the user confirms their new cost
+        for built in later {
+            let built = if discarded.contains(&built.tx_hash) {
+                wallet_rebuild_message(built, &mut info).await
+            } else {
+                Some(built)
+            };
+
+            if let Some(built) = built {
+                pending_sends.write().push(built);
+            }
         }
     };
 
+    // previews left open when leaving the chat are dropped, their transactions would hold the next nonces
+    use_drop(move || {
+        let previews = pending_sends.peek().clone();
+        if !previews.is_empty() {
+            spawn_forever(async move {
+                for built in previews {
+                    wallet_cancel_message(built).await;
+                }
+            });
+        }
+    });
+
     let remove_contact = move |_: FormEvent| async move {
         let address = contact_address.read().clone();
 
@@ -79,32 +312,30 @@ pub fn ChatView(name: String, address: String) -> Element {
         }
     };
 
-    // pool for new app events
+    // follow the app events, the dispatcher already stored the messages
     use_future(move || async move {
-        loop {
-            if let Some(wallet) = &*WALLET.read() {
-                // retrive the topoheight from the wallet
-                topoheight.set(wallet.read().await.topoheight);
+        let mut events = subscribe_app_events();
 
-                // check for app events
-                wallet.write().await.backgroud_daemon().await;
-
-                // store received messages in db
-                while let Some(tx) = wallet.write().await.rx_messages.pop() {
-                    // store the message
-                    db_store_init_message(tx).await;
-
-                    // reload the db
-                    db_message_handle.restart();
-                }
-
-                while let Some(message) = wallet.write().await.confirmed_messages.pop() {
-                    db_update_status_topoheight(message).await;
+        if let Some(wallet) = &*WALLET.read() {
+            topoheight.set(wallet.read().await.state.topoheight);
+        }
 
-                    *IS_READY.write().write().await = true;
-                    wallet_is_ready.set(true);
-                    db_message_handle.restart();
+        loop {
+            match events.recv().await {
+                Ok(AppEvent::NewTopoHeight(new_topoheight)) => topoheight.set(new_topoheight),
+                Ok(AppEvent::MessageReceived(message)) => {
+                    if message.address == *contact_address.read() {
+                        db_message_handle.restart();
+                    }
                 }
+                // a confirmed batch may hold messages to several contacts
+                Ok(AppEvent::MessageConfirmed(_))
+                | Ok(AppEvent::MessageOrphaned(_))
+                | Ok(AppEvent::MessagesChanged)
+                | Err(RecvError::Lagged(_)) => db_message_handle.restart(),
+                Ok(AppEvent::NewAsset) => wallet_assets_handle.restart(),
+                Ok(_) => {}
+                Err(RecvError::Closed) => break,
             }
         }
     });
@@ -156,29 +387,100 @@ pub fn ChatView(name: String, address: String) -> Element {
         main {
             class: "flex-1 overflow-auto outline-2 outline-green-700 rounded-xl m-4 h-full flex flex-col-reverse p-2",
             for msg in messages_from_db.cloned().iter().rev() {
-                if msg.message.is_some() {
-                    if msg.direction == "Outgoing" {
-                        div {
-                            class: "flex items-center justify-between",
+                // a payment sent without text still gets its bubble
+                if msg.message.is_some() || msg.amount > 0 {
+                    div {
+                        id: "msg-{msg.reference()}",
+                        // quoted message this one answers
+                        if let Some(reference) = msg.reply_to.clone() {
                             a {
-                                class: "text-green-600",
-                                "> {msg.message.as_ref().unwrap()}"
+                                class: "block text-sm text-green-900 border-l-2 border-green-900 pl-2 ml-2 cursor-pointer hover:text-green-700",
+                                onclick: {
+                                    let reference = reference.clone();
+                                    move |_| {
+                                        // the reference comes from the contact, it is sent as data
+                                        let eval = document::eval(
+                                            "const reference = await dioxus.recv(); document.getElementById('msg-' + reference)?.scrollIntoView({ behavior: 'smooth', block: 'center' })"
+                                        );
+                                        if let Err(e) = eval.send(reference.clone()) {
+                                            info!("Scroll to quote error: {e:?}");
+                                        }
+                                    }
+                                },
+                                "{quoted_snippet(&messages_from_db.read(), &reference)}"
                             }
-                            a {
-                                class: "text-green-900 mx-2",
-                                "{msg.status}", " {msg.topoheight}"
+                        }
+                        if msg.is_kind(MessageKind::PaymentRequest) {
+                            div {
+                                class: "flex items-center justify-between outline-2 outline-green-600 rounded-xl text-green-600 p-2 m-2",
+                                div {
+                                    p {
+                                        if msg.direction == MessageDirection::Outgoing { "> requested " } else { "< requests " }
+                                        "{request_label(msg, &assets.read())}"
+                                    }
+                                    p { class: "text-sm text-green-900", "{msg.message.as_deref().unwrap_or_default()}" }
+                                }
+                                {
+                                    match request_state(msg, &messages_from_db.read()) {
+                                        RequestState::Open if msg.direction != MessageDirection::Outgoing => rsx! {
+                                            button {
+                                                class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 hover:text-black px-4 py-2",
+                                                onclick: {
+                                                    let msg = msg.clone();
+                                                    move |_| pay_request(msg.clone())
+                                                },
+                                                "Pay"
+                                            }
+                                        },
+                                        state => rsx! { a { class: "text-green-900 mx-2", "{state.as_str()}" } },
+                                    }
+                                }
                             }
                         }
-                    } else {
                         div {
                             class: "flex items-center justify-between",
+                            // requests show their memo on the card
                             a {
                                 class: "text-green-600",
-                                "< {msg.message.as_ref().unwrap()}"
+                                if msg.is_kind(MessageKind::PaymentRequest) {
+                                    ""
+                                } else if msg.direction == MessageDirection::Outgoing {
+                                    "> {msg.message.as_deref().unwrap_or_default()}"
+                                } else {
+                                    "< {msg.message.as_deref().unwrap_or_default()}"
+                                }
                             }
-                            a {
-                                class: "text-green-900 mx-2",
-                                "{msg.status}", " {msg.topoheight}"
+                            div {
+                                class: "flex-none",
+                                if msg.amount > 0 {
+                                    a {
+                                        class: "outline-1 outline-green-600 rounded-xl text-green-500 px-2 mx-2",
+                                        "{payment_label(msg, &assets.read())}"
+                                    }
+                                }
+                                a {
+                                    class: "{status_class(msg.status)} mx-2",
+                                    title: "{msg.status:?} at {msg.topoheight}",
+                                    "{status_label(msg, *topoheight.read())}"
+                                }
+                                if msg.direction == MessageDirection::Outgoing && msg.status == MessageStatus::Failed {
+                                    button {
+                                        class: "text-green-900 hover:text-green-600 mx-2",
+                                        onclick: {
+                                            let msg = msg.clone();
+                                            move |_| retry_message(msg.clone())
+                                        },
+                                        "retry"
+                                    }
+                                }
+                                button {
+                                    class: "text-green-900 hover:text-green-600 mx-2",
+                                    onclick: {
+                                        let msg = msg.clone();
+                                        move |_| reply_to.set(Some(msg.clone()))
+                                    },
+                                    "reply"
+                                }
                             }
                         }
                     }
@@ -187,6 +489,51 @@ pub fn ChatView(name: String, address: String) -> Element {
         }
         footer {
             class: "mb-4",
+            for (i, built) in pending_sends().into_iter().enumerate() {
+                div {
+                    key: "{built.tx_hash}",
+                    class: "outline-2 outline-green-600 rounded-xl text-green-600 p-4 mx-4 mb-2",
+                    p { "Network fee: {format_xelis(built.network_fee)} XEL" }
+                    if let Some(dev_fee_address) = built.dev_fee_address {
+                        p { "Dev fee: {format_xelis(built.dev_fee)} XEL to {dev_fee_address}" }
+                    } else {
+                        p { "Dev fee: none" }
+                    }
+                    if built.db_message.amount > 0 {
+                        p { "Payment: {payment_label(&built.db_message, &assets.read())}" }
+                    }
+                    p { class: "font-semibold", "Total: {format_xelis(built.total_xelis())} XEL" }
+                    div {
+                        class: "flex justify-end mt-2",
+                        button {
+                            class: "outline-2 outline-green-700 rounded-xl px-4 py-2 mr-2 hover:text-green-500",
+                            onclick: move |_| cancel_send(i),
+                            "Cancel"
+                        }
+                        // the transactions go out in the order they were built
+                        if i == 0 {
+                            button {
+                                class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 hover:text-black px-4 py-2",
+                                onclick: confirm_send,
+                                "Confirm"
+                            }
+                        } else {
+                            a { class: "text-green-900 px-4 py-2", "after the message above" }
+                        }
+                    }
+                }
+            }
+            if let Some(original) = reply_to() {
+                div {
+                    class: "flex justify-between text-sm text-green-900 border-l-2 border-green-900 pl-2 mx-4 mb-2",
+                    a { "Replying to: {snippet(original.message.as_deref().unwrap_or_default())}" }
+                    button {
+                        class: "hover:text-green-600 mx-2",
+                        onclick: move |_| reply_to.set(None),
+                        "x"
+                    }
+                }
+            }
             form {
                 onsubmit: move |event| async move {
                     event.prevent_default();
@@ -205,11 +552,64 @@ pub fn ChatView(name: String, address: String) -> Element {
                     }
                     button {
                         class: "outline-2 outline-green-700 rounded-xl mr-4 p-4 text-green-600 hover:text-green-500 flex-none",
-                        disabled: !wallet_is_ready(),
+                        disabled: message_parts() > MAX_MESSAGE_PARTS,
                         r#type: "submit",
                         "Send"
                     }
                 }
+                div {
+                    class: "flex mt-2",
+                    input {
+                        id: "amount-input",
+                        class: "grow outline-2 outline-green-700 rounded-xl p-2 ml-4 text-green-600",
+                        r#type: "number",
+                        min: "0",
+                        step: "any",
+                        placeholder: "amount (optional)",
+                        value: "{send_amount}",
+                        oninput: move |event| send_amount.set(event.value())
+                    }
+                    select {
+                        id: "asset-select",
+                        class: "outline-2 outline-green-700 rounded-xl p-2 mx-4 text-green-600 bg-black flex-none",
+                        onchange: move |event| asset_hash.set(event.value()),
+                        // spam tokens cannot be picked
+                        for asset in assets.read().iter().filter(|asset| SETTINGS.read().is_asset_allowed(&asset.hash)) {
+                            option { value: "{asset.hash}", selected: asset.hash == *asset_hash.read(), "{asset.ticker}" }
+                        }
+                    }
+                }
+                div {
+                    class: "flex mt-2",
+                    select {
+                        id: "request-expiry",
+                        class: "grow outline-2 outline-green-700 rounded-xl p-2 ml-4 text-green-600 bg-black",
+                        onchange: move |event| {
+                            if let Ok(i) = event.value().parse::<usize>() {
+                                request_expiry.set(i);
+                            }
+                        },
+                        for (i, (label, _)) in REQUEST_EXPIRY_OPTIONS.iter().enumerate() {
+                            option { value: "{i}", selected: i == request_expiry(), "{label}" }
+                        }
+                    }
+                    button {
+                        class: "outline-2 outline-green-700 rounded-xl p-2 mx-4 text-green-600 hover:text-green-500 flex-none",
+                        r#type: "button",
+                        onclick: request_payment,
+                        "Request"
+                    }
+                }
+                div {
+                    class: "text-sm text-green-900 mx-4 mt-1",
+                    if message_parts() > MAX_MESSAGE_PARTS {
+                        "{message_len()}/{MESSAGE_PART_SIZE * MAX_MESSAGE_PARTS} bytes, message too long"
+                    } else if message_parts() > 1 {
+                        "{message_len()} bytes, sent in {message_parts()} parts of up to {MESSAGE_PART_SIZE} bytes"
+                    } else {
+                        "{message_len()}/{MESSAGE_PART_SIZE} bytes"
+                    }
+                }
             }
             div {
                 a {
@@ -220,3 +620,125 @@ pub fn ChatView(name: String, address: String) -> Element {
         }
     )
 }
+
+// Short preview of a message, used for quotes
+fn snippet(text: &str) -> String {
+    const SNIPPET_CHARS: usize = 48;
+
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+// Quote of the message a reply points to, by message id or tx hash
+fn quoted_snippet(messages: &[DbMessage], reference: &str) -> String {
+    match messages.iter().find(|message| {
+        message.message_id.as_deref() == Some(reference) || message.hash == reference
+    }) {
+        Some(original) => format!(
+            "| {}",
+            snippet(original.message.as_deref().unwrap_or_default())
+        ),
+        None => "| original message not found".to_string(),
+    }
+}
+
+// Payment bubble text, like "received 1.5 XEL"
+fn payment_label(message: &DbMessage, assets: &[AssetInfo]) -> String {
+    let verb = if message.direction == MessageDirection::Outgoing {
+        "sent"
+    } else {
+        "received"
+    };
+
+    match assets.iter().find(|asset| asset.hash == message.asset) {
+        Some(asset) => format!("{verb} {}", asset.format_amount(message.amount as u64)),
+        // unknown assets are shown in atomic units
+        None => format!(
+            "{verb} {} of {}",
+            message.amount,
+            message.asset.get(..8).unwrap_or(&message.asset)
+        ),
+    }
+}
+
+// Indicator of a message status, confirmed messages count their confirmations
+fn status_label(message: &DbMessage, topoheight: i64) -> String {
+    match message.status {
+        MessageStatus::Draft => "✎ draft".to_string(),
+        MessageStatus::Queued => "⧗ queued".to_string(),
+        MessageStatus::Built => "○ built".to_string(),
+        MessageStatus::Broadcast => "◔ broadcast".to_string(),
+        MessageStatus::InMempool => "◑ in mempool".to_string(),
+        MessageStatus::Confirmed => format!(
+            "◕ {}/{FINAL_CONFIRMATIONS} confirmations",
+            message.confirmations(topoheight).unwrap_or_default()
+        ),
+        MessageStatus::Final => "● final".to_string(),
+        MessageStatus::Failed => "✕ failed".to_string(),
+        MessageStatus::Orphaned => "⚠ orphaned".to_string(),
+    }
+}
+
+fn status_class(status: MessageStatus) -> &'static str {
+    match status {
+        MessageStatus::Failed => "text-red-600",
+        MessageStatus::Orphaned => "text-yellow-600",
+        MessageStatus::Final => "text-green-600",
+        _ => "text-green-900",
+    }
+}
+
+// Lifecycle of a payment request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RequestState {
+    Open,
+    Paid,
+    Expired,
+}
+
+impl RequestState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RequestState::Open => "awaiting payment",
+            RequestState::Paid => "paid",
+            RequestState::Expired => "expired",
+        }
+    }
+}
+
+// A request is paid once a payment replying to it covers the requested amount
+fn request_state(request: &DbMessage, messages: &[DbMessage]) -> RequestState {
+    let reference = request.reference();
+
+    let is_paid = messages.iter().any(|message| {
+        message.is_kind(MessageKind::Payment)
+            && message.reply_to.as_deref() == Some(reference.as_str())
+            && message.status != MessageStatus::Failed
+            && request.request_asset.as_deref() == Some(message.asset.as_str())
+            && Some(message.amount) >= request.request_amount
+    });
+
+    if is_paid {
+        RequestState::Paid
+    } else if request
+        .request_expires_at
+        .is_some_and(|expires_at| expires_at < Utc::now().timestamp())
+    {
+        RequestState::Expired
+    } else {
+        RequestState::Open
+    }
+}
+
+// Requested amount, like "1.5 XEL"
+fn request_label(request: &DbMessage, assets: &[AssetInfo]) -> String {
+    let amount = request.request_amount.unwrap_or_default();
+    let asset_hash = request.request_asset.clone().unwrap_or_default();
+
+    match assets.iter().find(|asset| asset.hash == asset_hash) {
+        Some(asset) => asset.format_amount(amount as u64),
+        None => format!("{amount} of {}", asset_hash.get(..8).unwrap_or(&asset_hash)),
+    }
+}
@@ -1,12 +1,19 @@
 use std::str::SplitWhitespace;
 
 use crate::{
-    DB, Route, WALLET,
-    database::db_fns::{db_add_contact, db_read_contacts, db_store_init_message},
-    views::DbContact,
-    wallet::{utils::NODE_ENDPOINT, wallet_fns::wallet_get_seed},
+    ACCOUNT, DB, Route, WALLET,
+    database::db_fns::{
+        DbUserLogin, db_add_contact, db_read_contacts, db_read_user, db_read_users,
+    },
+    views::{DbContact, accounts::switch_account},
+    wallet::{
+        events::{AppEvent, subscribe_app_events},
+        utils::NodeHealth,
+        wallet_fns::{wallet_connect_best_node, wallet_get_seed, wallet_import_history},
+    },
 };
 use dioxus::{logger::tracing::info, prelude::*};
+use tokio::sync::broadcast::error::RecvError;
 
 #[allow(
     clippy::redundant_closure,
@@ -21,10 +28,25 @@ pub fn Home() -> Element {
 
     let mut address = use_signal(|| String::new());
     let mut online_status = use_signal(|| String::new());
+    let mut sync_status = use_signal(|| String::new());
+    let mut active_node = use_signal(|| Option::<NodeHealth>::None);
     let mut balance = use_signal(|| String::new());
     let mut topoheight = use_signal(|| 0);
     let mut sidebar = use_signal(|| String::from("invisible"));
 
+    let mut accounts = use_signal(|| Vec::<DbUserLogin>::new());
+    let mut import_progress = use_signal(|| String::new());
+
+    // read accounts for the account picker
+    use_resource(move || async move {
+        if let Some(db) = &*DB.read() {
+            match db_read_users(db).await {
+                Ok(db_users) => accounts.set(db_users),
+                Err(e) => info!("Accounts error: {e}"),
+            }
+        }
+    });
+
     // read contacts from db
     let mut db_contacts = use_resource(move || async move {
         if let Some(db) = &*DB.read() {
@@ -34,6 +56,10 @@ pub fn Home() -> Element {
 
     // get state info
     use_future(move || async move {
+        // subscribe first so no event is missed while reading the wallet
+        let mut events = subscribe_app_events();
+        let mut needs_import = false;
+
         if let Some(wallet) = &*WALLET.read() {
             // get wallet address
             *address.write() = wallet.read().await.get_address().await;
@@ -48,24 +74,22 @@ pub fn Home() -> Element {
             )
             .to_string();
 
-            //set wallet online
-            match wallet
-                .write()
-                .await
-                .set_online(NODE_ENDPOINT.to_string())
-                .await
-            {
-                Ok(_) => online_status.set("Online".to_string()),
-                Err(e) => {
-                    if "Wallet is already in online mode" == e.to_string() {
-                        info!("set_online error: {e}");
+            //set wallet online on the healthiest node
+            if wallet.read().await.is_online().await {
+                online_status.set("Online".to_string());
+                active_node.set(wallet.read().await.active_node.clone());
+            } else {
+                match wallet_connect_best_node().await {
+                    Ok(node) => {
                         online_status.set("Online".to_string());
-                    } else {
+                        active_node.set(Some(node));
+                    }
+                    Err(e) => {
                         info!("set_online error: {e}");
                         online_status.set("Offline".to_string());
                     }
-                }
-            };
+                };
+            }
 
             // get balance
             if let Ok(ret_balance) = wallet.write().await.get_balance().await {
@@ -73,35 +97,56 @@ pub fn Home() -> Element {
             };
 
             // get topoheight
-            topoheight.set(wallet.read().await.topoheight);
+            topoheight.set(wallet.read().await.state.topoheight);
 
-            // poll for new app events
-            let mut refresh_db = false;
-            loop {
-                if let Some(wallet) = &*WALLET.read() {
-                    wallet.write().await.backgroud_daemon().await;
+            // restored accounts rebuild their chat history once the wallet is synced
+            needs_import = match (&*DB.read(), *ACCOUNT.read()) {
+                (Some(db), Some(account_id)) => db_read_user(db, account_id)
+                    .await
+                    .map(|db_user| !db_user.history_imported)
+                    .unwrap_or_default(),
+                _ => false,
+            };
 
-                    // retrive the topoheight from the wallet
-                    topoheight.set(wallet.read().await.topoheight);
+            // the history may have synced before this view subscribed
+            if needs_import && wallet.read().await.state.history_synced {
+                needs_import = false;
+                wallet_import_history(&mut import_progress).await;
+                db_contacts.restart();
+            }
+        }
 
-                    // retrive the wallet balance
-                    balance.set(wallet.read().await.balance.clone());
+        // follow the wallet through the app events, the dispatcher stores them
+        loop {
+            let event = events.recv().await;
 
-                    while let Some(tx) = wallet.write().await.rx_messages.pop() {
-                        // store the message
-                        db_store_init_message(tx).await;
-                        refresh_db = true;
-                    }
+            if let Some(status) = event.as_ref().ok().and_then(AppEvent::sync_status) {
+                sync_status.set(status);
+            }
 
-                    // reload the db
-                    if refresh_db {
-                        db_contacts.restart();
-                        refresh_db = false
-                    }
+            match event {
+                Ok(AppEvent::NewTopoHeight(new_topoheight)) => topoheight.set(new_topoheight),
+                Ok(AppEvent::Online) => online_status.set("Online".to_string()),
+                Ok(AppEvent::Offline) => online_status.set("Offline".to_string()),
+                // probed again or replaced by the session watcher
+                Ok(AppEvent::NodeChanged(node)) => active_node.set(Some(node)),
+                // orphaned and confirmed again messages
+                Ok(AppEvent::MessagesChanged) => db_contacts.restart(),
+                Ok(AppEvent::BalanceChanged(new_balance)) => balance.set(new_balance),
+                // a new contact may have written
+                Ok(AppEvent::MessageReceived(_)) => db_contacts.restart(),
+                Ok(AppEvent::HistorySynced) if needs_import => {
+                    needs_import = false;
+                    wallet_import_history(&mut import_progress).await;
+                    db_contacts.restart();
                 }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => db_contacts.restart(),
+                Err(RecvError::Closed) => break,
             }
         }
     });
+
     rsx!(
         div {
             class: "{sidebar} absolute bg-black text-green-600 outline-2 outline-green-700 w-80 min-h-screen overflow-y-auto transition-transform transform ease-in-out duration-300",
@@ -121,11 +166,38 @@ pub fn Home() -> Element {
                         "<"
                     }
                 }
+                ul {
+                    class: "m-4 pb-4 border-b border-green-900",
+                    for account in accounts.read().iter().cloned() {
+                        li {
+                            class: "mb-2",
+                            if Some(account.id) == *ACCOUNT.read() {
+                                a { class: "block text-green-500", "> {account.label}" }
+                            } else {
+                                button { class: "block hover:text-green-500", onclick: move |_| switch_account(account.id), "{account.label}" }
+                            }
+                        }
+                    }
+                    li { class: "mb-2", button { class: "block text-green-900 hover:text-green-500", onclick: move |_| {nav.push(Route::Accounts {});}, "Manage Accounts" } }
+                }
                 ul {
                     class: "m-4",
+                    li { class: "mb-2", button { class: "block hover:text-green-500", onclick: move |_| {nav.push(Route::WalletView {});}, "Wallet" } }
                     li { class: "mb-2", button { class: "block hover:text-green-500", onclick: move |_| {nav.push(Route::ViewSeed {});},"View Seed" } }
-                    li { class: "mb-2", button { class: "block hover:text-green-500", "Settings" } }
-                    li { class: "mb-2", button { class: "block hover:text-green-500", "Info" } }
+                    li { class: "mb-2", button { class: "block hover:text-green-500", onclick: move |_| {nav.push(Route::Nodes {});}, "Nodes" } }
+                    li { class: "mb-2", button { class: "block hover:text-green-500", onclick: move |_| {nav.push(Route::Settings {});}, "Settings" } }
+                    li {
+                        class: "mb-2",
+                        button {
+                            class: "block hover:text-green-500",
+                            onclick: move |_| async move {
+                                wallet_import_history(&mut import_progress).await;
+                                db_contacts.restart();
+                            },
+                            "Rebuild History"
+                        }
+                    }
+                    li { class: "mb-2", button { class: "block hover:text-green-500", onclick: move |_| {nav.push(Route::Unlock {});}, "Lock" } }
                 }
                 div {
                     class: "absolute bottom-0 left-0 m-2 text-s text-green-900",
@@ -155,13 +227,27 @@ pub fn Home() -> Element {
                         },
                         " {online_status.read()}"
                     }
+                    if let Some(node) = active_node() {
+                        h1 {
+                            class: if node.is_healthy { "text-sm text-green-600" } else { "text-sm text-green-900" },
+                            "{node.endpoint} ({node.latency_ms} ms, v{node.version})"
+                        }
+                    }
                     h1 { class: "text-xl font-semibold text-green-600", "|" }
                     h1 { class: "text-xl font-semibold text-green-600", "{topoheight.read()}" }
+                    h1 { class: "text-sm text-green-900", "{sync_status.read()}" }
                     h1 { class: "", "" }
                 }
             }
         }
 
+        if !import_progress.read().is_empty() {
+            div {
+                class: "mx-4 text-sm text-green-900",
+                "{import_progress}"
+            }
+        }
+
         // main
         div {
             class: "flex flex-col p-4",
@@ -1,13 +1,23 @@
+use dioxus::logger::tracing::info;
 use serde::{Deserialize, Serialize};
 use sqlx::{
     prelude::{FromRow, Type},
     Decode,
 };
+use uuid::Uuid;
 
+use crate::wallet::message::{Envelope, MessageKind};
+
+pub mod accounts;
 pub mod chat_view;
 pub mod home;
+pub mod nodes;
 pub mod restore_wallet_options;
+pub mod session;
+pub mod settings;
 pub mod splashscreen;
+pub mod unlock;
+pub mod wallet_view;
 
 #[derive(Serialize, Deserialize)]
 pub struct WalletCreateOpenArgs<'a> {
@@ -20,6 +30,13 @@ pub struct DbContact {
     pub name: String,
     pub address: String,
 }
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct DbNode {
+    pub endpoint: String,
+    pub network: String,
+    pub is_default: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct DbRemoveContact {
     address: String,
@@ -53,16 +70,247 @@ pub struct AppEvents {
     transactions: Vec<TxData>,
 }
 
+// Blocks on top of a confirmed message before it is final
+pub const FINAL_CONFIRMATIONS: i64 = 8;
+
+// Lifecycle of a message: Draft → Queued → Built → Broadcast → InMempool → Confirmed → Final.
+// A message can skip steps, Failed and Orphaned leave the path until a retry or a rescan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum MessageStatus {
+    // composed, no transaction yet
+    #[default]
+    Draft,
+    // waiting in the outbox
+    Queued,
+    // transaction built, not broadcast
+    Built,
+    // accepted by the node
+    Broadcast,
+    // held unconfirmed in the mempool of the node
+    InMempool,
+    // in a block at the message topoheight, confirmations are counted from it
+    Confirmed,
+    // `FINAL_CONFIRMATIONS` blocks deep
+    Final,
+    Failed,
+    // its block was dropped by a rescan
+    Orphaned,
+}
+
+impl MessageStatus {
+    pub const ALL: [MessageStatus; 9] = [
+        MessageStatus::Draft,
+        MessageStatus::Queued,
+        MessageStatus::Built,
+        MessageStatus::Broadcast,
+        MessageStatus::InMempool,
+        MessageStatus::Confirmed,
+        MessageStatus::Final,
+        MessageStatus::Failed,
+        MessageStatus::Orphaned,
+    ];
+
+    /// Whether the lifecycle allows a message to go from this status to `next`
+    pub fn can_become(self, next: MessageStatus) -> bool {
+        use MessageStatus::*;
+
+        match (self, next) {
+            (from, to) if from == to => true,
+            (Draft, Queued | Built) => true,
+            (Queued, Built | Broadcast | Failed) => true,
+            (Built, Broadcast | Queued | Failed) => true,
+            (Broadcast, InMempool | Confirmed | Queued | Failed) => true,
+            (InMempool, Confirmed | Failed) => true,
+            // a rescan orphans incoming messages and tracks outgoing ones again
+            (Confirmed | Final, Orphaned | Broadcast) => true,
+            (Confirmed, Final) => true,
+            (Failed, Queued) => true,
+            // an earlier attempt went through after all
+            (Queued | Failed, InMempool | Confirmed) => true,
+            (Orphaned, Confirmed) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum MessageDirection {
+    #[default]
+    Incoming,
+    Outgoing,
+}
+
 #[derive(Default, Serialize, Deserialize, Debug, Clone, FromRow, Type)]
 pub struct DbMessage {
-    pub status: String,
-    pub direction: String,
+    // local row id, stable across every update of the message
+    pub local_id: String,
+    pub status: MessageStatus,
+    pub direction: MessageDirection,
     pub address: String,
     pub hash: String,
+    // network fee
     pub fee: f64,
     pub timestamp: i64,
     pub topoheight: i64,
     pub asset: String,
     pub amount: i64,
     pub message: Option<String>,
+    // envelope id, none for legacy messages
+    pub message_id: Option<String>,
+    // message id or tx hash of the message this one answers
+    pub reply_to: Option<String>,
+    // envelope kind, see `MessageKind`
+    pub kind: String,
+    // payment request terms, amount in atomic units
+    pub request_amount: Option<i64>,
+    pub request_asset: Option<String>,
+    pub request_expires_at: Option<i64>,
+    // dev fee paid with the message, in XEL
+    pub dev_fee: f64,
+    // transfer carrying the first part, a transaction can carry several messages
+    pub transfer_index: i64,
+}
+
+// A message waiting in the outbox to be broadcast
+#[derive(Default, Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct DbOutboxItem {
+    // local id of the outgoing message
+    pub local_id: String,
+    pub attempts: i64,
+    // unix timestamp of the next attempt
+    pub next_attempt_at: i64,
+    // transaction built by the last attempt, reused while its nonce is free
+    pub tx_hash: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl DbMessage {
+    /// New local id for a message about to be stored
+    pub fn new_local_id() -> String {
+        Uuid::new_v4().simple().to_string()
+    }
+
+    /// Moves the message along its lifecycle, a transition it does not allow is ignored.
+    /// The database refuses it as well, every status update there checks `can_become`.
+    pub fn set_status(&mut self, status: MessageStatus) {
+        if self.status.can_become(status) {
+            self.status = status;
+        } else {
+            info!(
+                "Message {} cannot go from {:?} to {:?}",
+                self.local_id, self.status, status
+            );
+        }
+    }
+
+    /// Blocks on top of the block of a confirmed message, none before it is confirmed
+    pub fn confirmations(&self, topoheight: i64) -> Option<i64> {
+        match self.status {
+            MessageStatus::Confirmed | MessageStatus::Final => {
+                Some((topoheight - self.topoheight).max(0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fills in the message fields carried by an envelope
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.message_id = envelope.message_id();
+        self.kind = envelope.kind.as_str().to_string();
+        self.reply_to = envelope.reply_to;
+        self.request_amount = envelope.amount.map(|amount| amount as i64);
+        self.request_asset = envelope.asset;
+        self.request_expires_at = envelope.expires_at.map(|expires_at| expires_at as i64);
+        self.message = Some(envelope.body);
+    }
+
+    /// Envelope to send the message with, the inverse of `set_envelope`
+    pub fn to_envelope(&self) -> Envelope {
+        Envelope {
+            kind: MessageKind::parse(&self.kind).unwrap_or_default(),
+            reply_to: self.reply_to.clone(),
+            amount: self.request_amount.map(|amount| amount as u64),
+            asset: self.request_asset.clone(),
+            expires_at: self.request_expires_at.map(|expires_at| expires_at as u64),
+            ..Envelope::text(
+                self.message_id.as_deref().unwrap_or_default(),
+                self.message.as_deref().unwrap_or_default(),
+            )
+        }
+    }
+
+    pub fn is_kind(&self, kind: MessageKind) -> bool {
+        self.kind == kind.as_str()
+    }
+
+    /// Reference used by replies, the envelope id or the tx hash
+    pub fn reference(&self) -> String {
+        self.message_id.clone().unwrap_or_else(|| self.hash.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MessageStatus::*;
+
+    #[test]
+    fn messages_follow_the_lifecycle() {
+        for (from, to) in [
+            (Draft, Queued),
+            (Draft, Built),
+            (Queued, Built),
+            (Built, Broadcast),
+            (Broadcast, InMempool),
+            (InMempool, Confirmed),
+            (Broadcast, Confirmed),
+            (Confirmed, Final),
+            (Failed, Queued),
+            (Queued, InMempool),
+            (Failed, Confirmed),
+            (Confirmed, Orphaned),
+            (Final, Broadcast),
+            (Orphaned, Confirmed),
+        ] {
+            assert!(from.can_become(to), "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn messages_do_not_go_back() {
+        for (from, to) in [
+            (Final, Confirmed),
+            (Final, Failed),
+            (Confirmed, Failed),
+            (Confirmed, Queued),
+            (InMempool, Broadcast),
+            (Broadcast, Built),
+            (Failed, Built),
+            (Orphaned, Final),
+            (Built, Draft),
+        ] {
+            assert!(!from.can_become(to), "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn every_status_can_stay() {
+        for status in MessageStatus::ALL {
+            assert!(status.can_become(status));
+        }
+    }
+
+    #[test]
+    fn refused_transitions_leave_the_status() {
+        let mut message = DbMessage {
+            status: Final,
+            ..Default::default()
+        };
+
+        message.set_status(Failed);
+        assert_eq!(message.status, Final);
+
+        message.set_status(Orphaned);
+        assert_eq!(message.status, Orphaned);
+    }
 }
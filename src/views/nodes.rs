@@ -0,0 +1,168 @@
+use crate::{
+    DB, Route, WALLET,
+    database::db_fns::{db_add_node, db_read_nodes, db_remove_node},
+    views::DbNode,
+    wallet::{
+        utils::{NodeHealth, WalletNetwork, probe_node},
+        wallet_fns::wallet_connect_best_node,
+    },
+};
+use dioxus::{logger::tracing::info, prelude::*};
+
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+#[component]
+pub fn Nodes() -> Element {
+    let nav = navigator();
+
+    let mut network = use_signal(WalletNetwork::default);
+    let mut nodes_vec = use_signal(|| Vec::<(DbNode, NodeHealth)>::new());
+    let mut new_endpoint = use_signal(|| String::new());
+    let mut node_ret_msg = use_signal(|| String::new());
+
+    // read the node registry and probe every node
+    let mut db_nodes = use_resource(move || async move {
+        let wallet_network = match &*WALLET.read() {
+            Some(wallet) => wallet.read().await.network,
+            None => WalletNetwork::default(),
+        };
+        network.set(wallet_network);
+
+        if let Some(db) = &*DB.read() {
+            let nodes = db_read_nodes(db, wallet_network).await;
+
+            let healths = futures::future::join_all(
+                nodes
+                    .iter()
+                    .map(|node| probe_node(node.endpoint.clone(), wallet_network)),
+            )
+            .await;
+
+            nodes_vec.set(nodes.into_iter().zip(healths).collect());
+        }
+    });
+
+    let add_node = move |_: FormEvent| async move {
+        let endpoint = new_endpoint.read().trim().to_string();
+
+        if endpoint.is_empty() {
+            node_ret_msg.set("Node endpoint cannot be empty".to_string());
+            return;
+        }
+
+        let new_node = DbNode {
+            endpoint,
+            network: network().to_string(),
+            is_default: false,
+        };
+
+        if let Some(db) = &*DB.read() {
+            db_add_node(db, &mut node_ret_msg, new_node).await;
+        }
+
+        new_endpoint.set(String::new());
+        db_nodes.restart();
+    };
+
+    let reconnect = move |_| async move {
+        match wallet_connect_best_node().await {
+            Ok(node) => node_ret_msg.set(format!("Connected to {}", node.endpoint)),
+            Err(e) => {
+                info!("Reconnect error: {e}");
+                node_ret_msg.set(e.to_string());
+            }
+        }
+    };
+
+    rsx!(
+        div {
+            class: "outline-2 outline-green-700 rounded-xl mx-4 mt-4",
+            div {
+                class: "container mx-auto",
+                div {
+                    class: "flex justify-between items-center ",
+                    button {
+                        class: "text-xl font-semibold text-green-600 hover:text-green-500 p-4",
+                        id: "go-home",
+                        onclick: move |_| { nav.push(Route::Home {}); },
+                        "<"
+                    }
+                    h1 {
+                        class: "text-xl font-semibold text-green-600",
+                        "Nodes ({network})"
+                    }
+                    button {
+                        class: "text-xl text-green-600 hover:text-green-500 p-4",
+                        onclick: reconnect,
+                        "Reconnect"
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex flex-col p-4",
+            for (node, health) in nodes_vec.read().iter().cloned() {
+                div {
+                    class: "flex justify-between items-center outline-2 outline-green-700 rounded-xl p-4 mb-4 text-green-600",
+                    div {
+                        p { "{node.endpoint}" }
+                        if health.is_healthy {
+                            p { class: "text-sm", "{health.latency_ms} ms | v{health.version} | {health.topoheight}" }
+                        } else {
+                            p { class: "text-sm text-green-900", "{health.error.clone().unwrap_or_default()}" }
+                        }
+                    }
+                    if !node.is_default {
+                        button {
+                            class: "hover:text-green-500",
+                            onclick: move |_| {
+                                let endpoint = node.endpoint.clone();
+                                async move {
+                                    if let Some(db) = &*DB.read() {
+                                        db_remove_node(db, endpoint, network()).await;
+                                    }
+                                    db_nodes.restart();
+                                }
+                            },
+                            "Remove"
+                        }
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex items-center justify-center",
+            form {
+                class: "p-4 w-full max-w-md",
+                onsubmit: add_node,
+                div {
+                    class: "flex",
+                    input {
+                        id: "node-endpoint",
+                        class: "grow outline-2 outline-green-600 rounded-xl p-4 mb-4 text-green-600",
+                        placeholder: "Enter a node address...",
+                        value: "{new_endpoint}",
+                        oninput: move |event| new_endpoint.set(event.value())
+                    }
+                }
+                div {
+                    class: "flex justify-center",
+                    button {
+                        class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 text-green-600 hover:text-black p-4",
+                        r#type: "submit",
+                        "Add Node"
+                    }
+                }
+                div {
+                    class: "text-green-600",
+                    "{node_ret_msg.read()}"
+                }
+            }
+        }
+    )
+}
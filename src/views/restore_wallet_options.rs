@@ -1,6 +1,28 @@
-use crate::{database::db_fns::db_restore_wallet, Route};
+use crate::{database::db_fns::db_restore_wallet, wallet::utils::WalletNetwork, Route};
 use dioxus::prelude::*;
 
+/// Network picker shared by the create / restore forms
+#[component]
+fn NetworkSelect(mut network: Signal<WalletNetwork>) -> Element {
+    rsx!(
+        select {
+            id: "wallet-network-select",
+            onchange: move |event| {
+                if let Ok(selected) = event.value().parse::<WalletNetwork>() {
+                    network.set(selected);
+                }
+            },
+            for item in WalletNetwork::ALL {
+                option {
+                    value: "{item}",
+                    selected: item == network(),
+                    "{item}"
+                }
+            }
+        }
+    )
+}
+
 #[component]
 pub fn RestoreWalletOptions() -> Element {
     let nav = navigator();
@@ -18,6 +40,7 @@ pub fn CreateNewWallet() -> Element {
     let mut wallet_name = use_signal(|| String::new());
     let mut wallet_password = use_signal(|| String::new());
     let mut wallet_msg = use_signal(|| String::new());
+    let wallet_network = use_signal(WalletNetwork::default);
 
     let create_wallet = move |_: FormEvent| async move {
         let name = wallet_name.read().clone();
@@ -26,7 +49,7 @@ pub fn CreateNewWallet() -> Element {
         // if the name and password are not empty
         if !name.is_empty() && !password.is_empty() {
             // store the info as a wallet login
-            db_restore_wallet(name, password, None, None).await;
+            db_restore_wallet(name, password, wallet_network(), None, None).await;
         }
     };
 
@@ -49,6 +72,7 @@ pub fn CreateNewWallet() -> Element {
                     value: "{wallet_password}",
                     oninput: move |event| wallet_password.set(event.value())
                 }
+                NetworkSelect { network: wallet_network }
                 button { r#type: "submit", "Create Wallet" }
             }
             p { "{wallet_msg.read()}" }
@@ -62,6 +86,7 @@ pub fn RestoreFromSeed() -> Element {
     let mut wallet_name = use_signal(|| String::new());
     let mut wallet_password = use_signal(|| String::new());
     let mut wallet_seed = use_signal(|| String::new());
+    let wallet_network = use_signal(WalletNetwork::default);
 
     let restore_wallet = move |_: FormEvent| async move {
         let name = wallet_name.read().clone();
@@ -71,7 +96,7 @@ pub fn RestoreFromSeed() -> Element {
         // if the name and password are not empty
         if !name.is_empty() && !password.is_empty() {
             // store the info as a wallet login
-            db_restore_wallet(name, password, Some(wallet_seed), None).await;
+            db_restore_wallet(name, password, wallet_network(), Some(wallet_seed), None).await;
         }
     };
 
@@ -101,6 +126,7 @@ pub fn RestoreFromSeed() -> Element {
                     value: "{wallet_seed}",
                     oninput: move |event| wallet_seed.set(event.value())
                 }
+                NetworkSelect { network: wallet_network }
                 button { r#type: "submit", "Restore Wallet" }
             }
         }
@@ -113,6 +139,7 @@ pub fn RestoreFromPrivateKey() -> Element {
     let mut wallet_name = use_signal(|| String::new());
     let mut wallet_password = use_signal(|| String::new());
     let mut wallet_private_key = use_signal(|| String::new());
+    let wallet_network = use_signal(WalletNetwork::default);
 
     let restore_wallet = move |_: FormEvent| async move {
         let name = wallet_name.read().clone();
@@ -122,7 +149,14 @@ pub fn RestoreFromPrivateKey() -> Element {
         // if the name and password are not empty
         if !name.is_empty() && !password.is_empty() {
             // store the info as a wallet login
-            db_restore_wallet(name, password, None, Some(wallet_private_key)).await;
+            db_restore_wallet(
+                name,
+                password,
+                wallet_network(),
+                None,
+                Some(wallet_private_key),
+            )
+            .await;
         }
     };
 
@@ -152,6 +186,7 @@ pub fn RestoreFromPrivateKey() -> Element {
                     value: "{wallet_private_key}",
                     oninput: move |event| wallet_private_key.set(event.value())
                 }
+                NetworkSelect { network: wallet_network }
                 button { r#type: "submit", "Restore Wallet" }
             }
         }
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    LAST_ACTIVITY, Route, SETTINGS, WALLET,
+    wallet::wallet_fns::{
+        wallet_check_active_node, wallet_connect_best_node, wallet_process_outbox,
+    },
+};
+use dioxus::{logger::tracing::info, prelude::*};
+
+// Interval between two idle checks
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Interval between two runs of the outbox worker
+const OUTBOX_INTERVAL: Duration = Duration::from_secs(5);
+// Interval between two health probes of the active node
+const NODE_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Layout of the unlocked session, routes to the unlock screen when idle for too long
+#[component]
+pub fn Session() -> Element {
+    let nav = navigator();
+
+    // a locked wallet cannot reach the session routes
+    use_hook(move || {
+        *LAST_ACTIVITY.write() = Instant::now();
+
+        if WALLET.read().is_none() {
+            nav.replace(Route::Unlock {});
+        }
+    });
+
+    // lock the session after the configured idle time
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+
+            let timeout = SETTINGS.read().lock_timeout_secs;
+            if timeout > 0 && LAST_ACTIVITY.read().elapsed() >= Duration::from_secs(timeout) {
+                info!("Session idle for {timeout}s, locking");
+                nav.replace(Route::Unlock {});
+                break;
+            }
+        }
+    });
+
+    // send the queued messages once the wallet is online
+    use_future(move || async move {
+        loop {
+            wallet_process_outbox().await;
+            tokio::time::sleep(OUTBOX_INTERVAL).await;
+        }
+    });
+
+    // watch the active node and fail over to the next healthy one, whatever the view
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(NODE_HEALTH_INTERVAL).await;
+
+            if !wallet_check_active_node().await {
+                info!("Active node is down, failing over");
+
+                if let Err(e) = wallet_connect_best_node().await {
+                    info!("Failover error: {e}");
+                }
+            }
+        }
+    });
+
+    rsx!(
+        div {
+            class: "flex flex-col h-full w-full",
+            onmousemove: move |_| *LAST_ACTIVITY.write() = Instant::now(),
+            onkeydown: move |_| *LAST_ACTIVITY.write() = Instant::now(),
+            onclick: move |_| *LAST_ACTIVITY.write() = Instant::now(),
+            // touch and wheel bubble up from the scrolled chat, scroll events do not
+            ontouchstart: move |_| *LAST_ACTIVITY.write() = Instant::now(),
+            onwheel: move |_| *LAST_ACTIVITY.write() = Instant::now(),
+            Outlet::<Route> {}
+        }
+    )
+}
@@ -0,0 +1,224 @@
+use crate::{
+    DB, Route, SETTINGS,
+    database::db_fns::db_save_settings,
+    settings::{AppSettings, Theme},
+    wallet::utils::{MnemonicLanguage, TableSize},
+};
+use dioxus::{logger::tracing::info, prelude::*};
+
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+#[component]
+pub fn Settings() -> Element {
+    let nav = navigator();
+
+    let mut settings = use_signal(|| SETTINGS.read().clone());
+    let mut settings_ret_msg = use_signal(|| String::new());
+
+    let save_settings = move |_: FormEvent| async move {
+        let new_settings: AppSettings = settings.read().clone();
+
+        if new_settings.n_threads == 0 {
+            settings_ret_msg.set("Worker threads must be at least 1".to_string());
+            return;
+        }
+
+        if new_settings
+            .dev_fee_amount
+            .is_some_and(|amount| !amount.is_finite() || amount < 0.0)
+        {
+            settings_ret_msg.set("Dev fee cannot be negative".to_string());
+            return;
+        }
+
+        match &*DB.read() {
+            Some(db) => match db_save_settings(db, &new_settings).await {
+                Ok(_) => {
+                    *SETTINGS.write() = new_settings;
+                    settings_ret_msg
+                        .set("Settings saved, table size and threads apply on next start".to_string());
+                }
+                Err(e) => {
+                    info!("Error saving settings: {e}");
+                    settings_ret_msg.set(format!("Error saving settings: {e}"));
+                }
+            },
+            None => settings_ret_msg.set("DB not accessible".to_string()),
+        }
+    };
+
+    rsx!(
+        div {
+            class: "outline-2 outline-green-700 rounded-xl mx-4 mt-4",
+            div {
+                class: "container mx-auto",
+                div {
+                    class: "flex justify-between items-center ",
+                    button {
+                        class: "text-xl font-semibold text-green-600 hover:text-green-500 p-4",
+                        id: "go-home",
+                        onclick: move |_| { nav.push(Route::Home {}); },
+                        "<"
+                    }
+                    h1 {
+                        class: "text-xl font-semibold text-green-600",
+                        "Settings"
+                    }
+                    h1 {
+                        class: "text-xl",
+                        ""
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex items-center justify-center",
+            form {
+                class: "p-4 w-full max-w-md text-green-600",
+                onsubmit: save_settings,
+                label { class: "block mb-2", "Node endpoint" }
+                input {
+                    id: "settings-node",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4",
+                    placeholder: "Automatic (node registry)",
+                    value: "{settings.read().node_endpoint.clone().unwrap_or_default()}",
+                    oninput: move |event| {
+                        let endpoint = event.value().trim().to_string();
+                        settings.write().node_endpoint = (!endpoint.is_empty()).then_some(endpoint);
+                    }
+                }
+                label { class: "block mb-2", "Precomputed table size" }
+                select {
+                    id: "settings-table-size",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4 bg-black",
+                    onchange: move |event| {
+                        if let Some(size) = event.value().parse::<usize>().ok().and_then(|i| TableSize::ALL.get(i)) {
+                            settings.write().table_size = *size;
+                        }
+                    },
+                    for (i, size) in TableSize::ALL.iter().enumerate() {
+                        option { value: "{i}", selected: *size == settings.read().table_size, "{size:?}" }
+                    }
+                }
+                label { class: "block mb-2", "Worker threads" }
+                input {
+                    id: "settings-threads",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4",
+                    r#type: "number",
+                    min: "1",
+                    value: "{settings.read().n_threads}",
+                    oninput: move |event| {
+                        if let Ok(n_threads) = event.value().parse::<usize>() {
+                            settings.write().n_threads = n_threads;
+                        }
+                    }
+                }
+                label { class: "block mb-2", "Mnemonic language" }
+                select {
+                    id: "settings-language",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4 bg-black",
+                    onchange: move |event| {
+                        if let Some(language) = event.value().parse::<usize>().ok().and_then(|i| MnemonicLanguage::ALL.get(i)) {
+                            settings.write().mnemonic_language = *language;
+                        }
+                    },
+                    for (i, language) in MnemonicLanguage::ALL.iter().enumerate() {
+                        option { value: "{i}", selected: *language == settings.read().mnemonic_language, "{language:?}" }
+                    }
+                }
+                label { class: "block mb-2", "Theme" }
+                select {
+                    id: "settings-theme",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4 bg-black",
+                    onchange: move |event| {
+                        if let Some(theme) = event.value().parse::<usize>().ok().and_then(|i| Theme::ALL.get(i)) {
+                            settings.write().theme = *theme;
+                        }
+                    },
+                    for (i, theme) in Theme::ALL.iter().enumerate() {
+                        option { value: "{i}", selected: *theme == settings.read().theme, "{theme:?}" }
+                    }
+                }
+                label { class: "block mb-2", "Auto-lock after (seconds, 0 never locks)" }
+                input {
+                    id: "settings-lock-timeout",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4",
+                    r#type: "number",
+                    min: "0",
+                    value: "{settings.read().lock_timeout_secs}",
+                    oninput: move |event| {
+                        if let Ok(lock_timeout_secs) = event.value().parse::<u64>() {
+                            settings.write().lock_timeout_secs = lock_timeout_secs;
+                        }
+                    }
+                }
+                div {
+                    class: "flex items-center mb-4",
+                    input {
+                        id: "settings-dev-fee",
+                        class: "mr-2",
+                        r#type: "checkbox",
+                        checked: settings.read().dev_fee_enabled,
+                        onchange: move |event| settings.write().dev_fee_enabled = event.checked()
+                    }
+                    label { "Support development with the dev fee" }
+                }
+                label { class: "block mb-2", "Dev fee per message (XEL)" }
+                input {
+                    id: "settings-dev-fee-amount",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4",
+                    r#type: "number",
+                    min: "0",
+                    step: "any",
+                    placeholder: "Network default",
+                    disabled: !settings.read().dev_fee_enabled,
+                    value: "{settings.read().dev_fee_amount.map(|amount| amount.to_string()).unwrap_or_default()}",
+                    oninput: move |event| {
+                        settings.write().dev_fee_amount = event.value().trim().parse::<f64>().ok();
+                    }
+                }
+                div {
+                    class: "flex items-center mb-4",
+                    input {
+                        id: "settings-batch-messages",
+                        class: "mr-2",
+                        r#type: "checkbox",
+                        checked: settings.read().batch_messages,
+                        onchange: move |event| settings.write().batch_messages = event.checked()
+                    }
+                    label { "Batch messages in one transaction, fees are not previewed" }
+                }
+                label { class: "block mb-2", "Batch window (seconds)" }
+                input {
+                    id: "settings-batch-window",
+                    class: "w-full outline-2 outline-green-600 rounded-xl p-4 mb-4",
+                    r#type: "number",
+                    min: "0",
+                    disabled: !settings.read().batch_messages,
+                    value: "{settings.read().batch_window_secs}",
+                    oninput: move |event| {
+                        if let Ok(batch_window_secs) = event.value().parse::<u64>() {
+                            settings.write().batch_window_secs = batch_window_secs;
+                        }
+                    }
+                }
+                div {
+                    class: "flex justify-center",
+                    button {
+                        class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 text-green-600 hover:text-black p-4",
+                        r#type: "submit",
+                        "Save"
+                    }
+                }
+                div {
+                    class: "text-green-600",
+                    "{settings_ret_msg.read()}"
+                }
+            }
+        }
+    )
+}
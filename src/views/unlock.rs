@@ -0,0 +1,122 @@
+use crate::{
+    ACCOUNT, DB, Route,
+    database::db_fns::{DbUserLogin, db_connect, db_read_users, db_unlock_wallet},
+    wallet::wallet_fns::wallet_lock,
+};
+use dioxus::{logger::tracing::info, prelude::*};
+
+#[allow(clippy::redundant_closure, clippy::await_holding_invalid_type)]
+#[component]
+pub fn Unlock() -> Element {
+    let nav = navigator();
+
+    let mut wallet_password = use_signal(|| String::new());
+    let mut unlock_msg = use_signal(|| String::new());
+    let mut accounts = use_signal(|| Vec::<DbUserLogin>::new());
+
+    // reaching the unlock screen locks the session
+    use_future(move || async move {
+        wallet_lock().await;
+
+        // the account list is read with a short lived connection
+        if let Err(e) = db_connect().await {
+            info!("Database error: {e}");
+            return;
+        }
+
+        let db = DB.write().take();
+        if let Some(db) = db {
+            match db_read_users(&db).await {
+                Ok(db_users) => {
+                    if ACCOUNT.read().is_none() {
+                        *ACCOUNT.write() = db_users.first().map(|user| user.id);
+                    }
+                    accounts.set(db_users);
+                }
+                Err(e) => info!("Accounts error: {e}"),
+            }
+            db.close().await;
+        }
+    });
+
+    let unlock_wallet = move |_: FormEvent| async move {
+        let password = wallet_password.read().clone();
+        wallet_password.set(String::new());
+
+        let Some(account_id) = *ACCOUNT.read() else {
+            unlock_msg.set("No account selected".to_string());
+            return;
+        };
+
+        if password.is_empty() {
+            unlock_msg.set("Password cannot be empty".to_string());
+            return;
+        }
+
+        unlock_msg.set("Unlocking...".to_string());
+        db_unlock_wallet(account_id, password, &mut unlock_msg).await;
+    };
+
+    rsx!(
+        div {
+            class: "h-screen flex items-center justify-center",
+            form {
+                class: "p-4 w-full max-w-md",
+                onsubmit: unlock_wallet,
+                h1 {
+                    class: "text-2xl font-semibold text-green-600 mb-4 text-center",
+                    "XELITE"
+                }
+                div {
+                    class: "flex",
+                    select {
+                        id: "unlock-account",
+                        class: "grow outline-2 outline-green-600 rounded-xl p-4 mb-4 text-green-600 bg-black",
+                        onchange: move |event| {
+                            if let Ok(account_id) = event.value().parse::<i64>() {
+                                *ACCOUNT.write() = Some(account_id);
+                            }
+                        },
+                        for account in accounts.read().iter() {
+                            option {
+                                value: "{account.id}",
+                                selected: Some(account.id) == *ACCOUNT.read(),
+                                "{account.label} ({account.network})"
+                            }
+                        }
+                    }
+                }
+                div {
+                    class: "flex",
+                    input {
+                        id: "unlock-password",
+                        class: "grow outline-2 outline-green-600 rounded-xl p-4 mb-4 text-green-600",
+                        r#type: "password",
+                        placeholder: "Enter wallet password...",
+                        value: "{wallet_password}",
+                        autofocus: true,
+                        oninput: move |event| wallet_password.set(event.value())
+                    }
+                }
+                div {
+                    class: "flex justify-between",
+                    button {
+                        class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 text-green-600 hover:text-black p-4",
+                        r#type: "button",
+                        onclick: move |_| { nav.push(Route::RestoreWalletOptions {}); },
+                        "Add Account"
+                    }
+                    button {
+                        class: "outline-2 outline-green-500 rounded-xl hover:bg-green-600 text-green-600 hover:text-black p-4",
+                        r#type: "submit",
+                        "Unlock"
+                    }
+                }
+                div {
+                    class: "text-green-600",
+                    "{unlock_msg.read()}"
+                }
+            }
+        }
+    )
+}
@@ -0,0 +1,181 @@
+use crate::{
+    ACCOUNT, DB, Route, SETTINGS, WALLET,
+    database::db_fns::{db_save_settings, db_total_dev_fees},
+    wallet::{
+        events::{AppEvent, subscribe_app_events},
+        utils::AssetBalance,
+    },
+};
+use dioxus::{logger::tracing::info, prelude::*};
+use tokio::sync::broadcast::error::RecvError;
+
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+#[component]
+pub fn WalletView() -> Element {
+    let nav = navigator();
+
+    let mut balances = use_signal(|| Vec::<AssetBalance>::new());
+    let mut wallet_ret_msg = use_signal(|| String::new());
+    let mut total_dev_fees = use_signal(|| 0.0);
+
+    // read the balance of every asset in wallet storage
+    let mut balances_handle = use_future(move || async move {
+        if let Some(wallet) = &*WALLET.read() {
+            match wallet.write().await.get_balances().await {
+                Ok(asset_balances) => balances.set(asset_balances),
+                Err(e) => {
+                    info!("Balances read error: {e}");
+                    wallet_ret_msg.set(format!("Balances read error: {e}"));
+                }
+            }
+        }
+    });
+
+    // read them again when the wallet sees a new asset or balance
+    use_future(move || async move {
+        let mut events = subscribe_app_events();
+
+        loop {
+            match events.recv().await {
+                Ok(AppEvent::NewAsset)
+                | Ok(AppEvent::BalanceChanged(_))
+                | Err(RecvError::Lagged(_)) => balances_handle.restart(),
+                Ok(_) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // dev fees paid by the account so far
+    use_future(move || async move {
+        let Some(account_id) = *ACCOUNT.read() else {
+            return;
+        };
+
+        if let Some(db) = &*DB.read() {
+            match db_total_dev_fees(db, account_id).await {
+                Ok(total) => total_dev_fees.set(total),
+                Err(e) => info!("Dev fees read error: {e}"),
+            }
+        }
+    });
+
+    // adds or removes an asset from the allow-list
+    let toggle_asset = move |asset_hash: String| async move {
+        let mut new_settings = SETTINGS.read().clone();
+
+        if new_settings.is_asset_allowed(&asset_hash) {
+            new_settings.allowed_assets.retain(|hash| *hash != asset_hash);
+        } else {
+            new_settings.allowed_assets.push(asset_hash);
+        }
+
+        match &*DB.read() {
+            Some(db) => match db_save_settings(db, &new_settings).await {
+                Ok(_) => *SETTINGS.write() = new_settings,
+                Err(e) => {
+                    info!("Error saving settings: {e}");
+                    wallet_ret_msg.set(format!("Error saving settings: {e}"));
+                }
+            },
+            None => wallet_ret_msg.set("DB not accessible".to_string()),
+        }
+    };
+
+    let allowed = balances
+        .read()
+        .iter()
+        .filter(|balance| SETTINGS.read().is_asset_allowed(&balance.asset.hash))
+        .cloned()
+        .collect::<Vec<_>>();
+    let hidden = balances
+        .read()
+        .iter()
+        .filter(|balance| !SETTINGS.read().is_asset_allowed(&balance.asset.hash))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    rsx!(
+        div {
+            class: "outline-2 outline-green-700 rounded-xl mx-4 mt-4",
+            div {
+                class: "container mx-auto",
+                div {
+                    class: "flex justify-between items-center ",
+                    button {
+                        class: "text-xl font-semibold text-green-600 hover:text-green-500 p-4",
+                        id: "go-home",
+                        onclick: move |_| { nav.push(Route::Home {}); },
+                        "<"
+                    }
+                    h1 {
+                        class: "text-xl font-semibold text-green-600",
+                        "Wallet"
+                    }
+                    h1 {
+                        class: "text-xl",
+                        ""
+                    }
+                }
+            }
+        }
+
+        div {
+            class: "flex flex-col p-4 text-green-600",
+            for balance in allowed {
+                div {
+                    class: "flex justify-between items-center outline-2 outline-green-700 rounded-xl p-4 mb-4",
+                    div {
+                        p { "{balance.asset.format_amount(balance.balance)}" }
+                        p { class: "text-sm text-green-900", "{balance.asset.name}" }
+                    }
+                    if SETTINGS.read().allowed_assets.contains(&balance.asset.hash) {
+                        button {
+                            class: "hover:text-green-500",
+                            onclick: {
+                                let asset_hash = balance.asset.hash.clone();
+                                move |_| toggle_asset(asset_hash.clone())
+                            },
+                            "Hide"
+                        }
+                    }
+                }
+            }
+
+            if !hidden.is_empty() {
+                h2 { class: "text-green-900 mb-2", "Hidden assets" }
+            }
+            for balance in hidden {
+                div {
+                    class: "flex justify-between items-center outline-2 outline-green-900 rounded-xl p-4 mb-4 text-green-900",
+                    div {
+                        p { "{balance.asset.format_amount(balance.balance)}" }
+                        p { class: "text-sm", "{balance.asset.name} | {balance.asset.hash}" }
+                    }
+                    button {
+                        class: "hover:text-green-500",
+                        onclick: {
+                            let asset_hash = balance.asset.hash.clone();
+                            move |_| toggle_asset(asset_hash.clone())
+                        },
+                        "Show"
+                    }
+                }
+            }
+
+            p {
+                class: "text-sm text-green-900 mb-4",
+                "Dev fees paid: {total_dev_fees} XEL"
+            }
+
+            div {
+                class: "text-green-600",
+                "{wallet_ret_msg.read()}"
+            }
+        }
+    )
+}
@@ -0,0 +1,177 @@
+//! Wallet event dispatcher.
+//!
+//! A single task owns the wallet event receiver while the wallet is open. It
+//! applies every event to the `ChatWallet` state, stores what changed in the
+//! database, then publishes an `AppEvent` to the views subscribed with
+//! `subscribe_app_events`. Views never wait on the wallet for events.
+
+use std::sync::LazyLock;
+
+use dioxus::{logger::tracing::info, prelude::*};
+use tokio::sync::broadcast::{self, Receiver, Sender, error::RecvError};
+
+use super::{
+    utils::NodeHealth,
+    wallet_fns::{wallet_reconcile_pending, wallet_rescan_messages},
+};
+use crate::{
+    ACCOUNT, DB, WALLET,
+    database::db_fns::{db_finalize_messages, db_store_init_message, db_update_status_topoheight},
+    views::DbMessage,
+};
+
+// Events kept for a slow subscriber before it misses some
+const APP_EVENTS_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug)]
+pub enum AppEvent {
+    // an incoming message, already stored
+    MessageReceived(DbMessage),
+    // the outgoing messages of a transaction included in a block, already stored
+    MessageConfirmed(DbMessage),
+    // the messages of a transaction orphaned or removed from the chain, already stored
+    MessageOrphaned(DbMessage),
+    // messages changed outside of wallet events, by the outbox or the reconciliation
+    MessagesChanged,
+    NewTopoHeight(i64),
+    // formatted XELIS balance
+    BalanceChanged(String),
+    // an asset the wallet had not seen yet
+    NewAsset,
+    // connection to the node
+    Online,
+    Offline,
+    // the active node was probed again or replaced
+    NodeChanged(NodeHealth),
+    // the wallet syncs again from this topoheight
+    Rescan(u64),
+    HistorySynced,
+    SyncError(String),
+}
+
+impl AppEvent {
+    /// Sync status shown for the events that change it
+    pub fn sync_status(&self) -> Option<String> {
+        match self {
+            AppEvent::Rescan(start_topoheight) => {
+                Some(format!("Rescanning from {start_topoheight}"))
+            }
+            AppEvent::HistorySynced => Some("Synced".to_string()),
+            AppEvent::SyncError(message) => Some(format!("Sync error: {message}")),
+            AppEvent::Offline => Some("Not syncing".to_string()),
+            _ => None,
+        }
+    }
+}
+
+static APP_EVENTS: LazyLock<Sender<AppEvent>> =
+    LazyLock::new(|| broadcast::channel(APP_EVENTS_CAPACITY).0);
+
+// Dispatcher task of the open wallet
+static DISPATCHER: GlobalSignal<Option<Task>> = Signal::global(|| None);
+
+/// Receives the app events published from now on
+pub fn subscribe_app_events() -> Receiver<AppEvent> {
+    APP_EVENTS.subscribe()
+}
+
+/// Publishes an app event to every subscribed view
+pub fn publish_app_event(event: AppEvent) {
+    // no view may be listening
+    let _ = APP_EVENTS.send(event);
+}
+
+/// Starts the event dispatcher of the open wallet, replacing the one of the previous wallet
+pub fn start_event_dispatcher() {
+    stop_event_dispatcher();
+
+    // not tied to the view that opened the wallet
+    *DISPATCHER.write() = spawn_forever(dispatch_wallet_events());
+}
+
+/// Stops the event dispatcher, the wallet is being closed
+pub fn stop_event_dispatcher() {
+    if let Some(task) = DISPATCHER.write().take() {
+        task.cancel();
+    }
+}
+
+#[allow(clippy::await_holding_invalid_type)]
+async fn dispatch_wallet_events() {
+    let mut receiver = match &*WALLET.read() {
+        Some(wallet) => {
+            let wallet = wallet.read().await.get_wallet().await.clone();
+            wallet.subscribe_events().await
+        }
+        None => return,
+    };
+
+    info!("Wallet event dispatcher started");
+
+    loop {
+        // the wallet lock is only taken once an event arrived
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                info!("Wallet event dispatcher lagged, {skipped} events skipped");
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let app_events = match &*WALLET.read() {
+            Some(wallet) => wallet.write().await.handle_event(event).await,
+            None => break,
+        };
+
+        for app_event in app_events {
+            store_app_event(&app_event).await;
+            publish_app_event(app_event);
+        }
+    }
+
+    info!("Wallet event dispatcher stopped");
+}
+
+// Stores the change carried by an app event before the views hear about it
+#[allow(clippy::await_holding_invalid_type)]
+async fn store_app_event(app_event: &AppEvent) {
+    match app_event {
+        AppEvent::MessageReceived(message) => db_store_init_message(message.clone()).await,
+        AppEvent::MessageConfirmed(message) | AppEvent::MessageOrphaned(message) => {
+            db_update_status_topoheight(message.clone()).await
+        }
+        AppEvent::HistorySynced => {
+            // settle messages left unconfirmed by the last session
+            let is_reconciled = match &*WALLET.read() {
+                Some(wallet) => wallet.read().await.pending_reconciled,
+                None => true,
+            };
+
+            if !is_reconciled {
+                wallet_reconcile_pending().await;
+                publish_app_event(AppEvent::MessagesChanged);
+            }
+        }
+        AppEvent::NewTopoHeight(topoheight) => {
+            // confirmed messages deep enough become final
+            let finalized = match (&*DB.read(), *ACCOUNT.read()) {
+                (Some(db), Some(account_id)) => {
+                    db_finalize_messages(db, account_id, *topoheight).await
+                }
+                _ => return,
+            };
+
+            match finalized {
+                Ok(0) => {}
+                Ok(_) => publish_app_event(AppEvent::MessagesChanged),
+                Err(e) => info!("Finalize error: {e}"),
+            }
+        }
+        AppEvent::Rescan(start_topoheight) => {
+            wallet_rescan_messages(*start_topoheight).await;
+            publish_app_event(AppEvent::MessagesChanged);
+        }
+        _ => {}
+    }
+}
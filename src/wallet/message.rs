@@ -0,0 +1,589 @@
+//! Chat message envelope carried in transfer extra data.
+//!
+//! Every transfer holds one envelope, a `DataElement` map with the protocol
+//! version, the message id, its kind and the body. Long messages are split in
+//! numbered parts sharing the same id. Replies carry the id of the message
+//! they answer, or its tx hash when it has no id. Payment requests carry the
+//! requested amount and asset, the payment answering one replies to it.
+//! Bare strings sent by older versions or other wallets are read as legacy
+//! text messages, and short plain text is still sent as a bare string.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use xelis_common::api::{DataElement, DataValue};
+
+// Version written in every envelope, newer versions are read on a best effort basis
+pub const PROTOCOL_VERSION: u8 = 1;
+
+// The protocol limits extra data to 1024 bytes per transfer,
+// the rest is left for the encryption and the envelope fields
+pub const MESSAGE_PART_SIZE: usize = 768;
+// Parts that fit in the extra data limit of a single transaction
+pub const MAX_MESSAGE_PARTS: usize = 16;
+
+const KEY_VERSION: &str = "v";
+const KEY_ID: &str = "id";
+const KEY_KIND: &str = "kind";
+const KEY_BODY: &str = "body";
+const KEY_PART: &str = "part";
+const KEY_PARTS: &str = "parts";
+const KEY_REPLY_TO: &str = "re";
+const KEY_AMOUNT: &str = "amount";
+const KEY_ASSET: &str = "asset";
+const KEY_EXPIRES_AT: &str = "expires";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    // asks the contact for an amount, the body is the memo
+    PaymentRequest,
+    // pays the request it replies to
+    Payment,
+}
+
+impl MessageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Text => "text",
+            MessageKind::PaymentRequest => "payment_request",
+            MessageKind::Payment => "payment",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<MessageKind> {
+        match kind {
+            "text" => Some(MessageKind::Text),
+            "payment_request" => Some(MessageKind::PaymentRequest),
+            "payment" => Some(MessageKind::Payment),
+            _ => None,
+        }
+    }
+}
+
+// A message, or one numbered part of it, as carried by a transfer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    // empty for legacy messages
+    pub id: String,
+    pub kind: MessageKind,
+    pub body: String,
+    pub part: u8,
+    pub parts: u8,
+    // message id or tx hash of the message this one answers
+    pub reply_to: Option<String>,
+    // requested amount in atomic units and asset of a payment request
+    pub amount: Option<u64>,
+    pub asset: Option<String>,
+    // unix timestamp after which a payment request cannot be paid
+    pub expires_at: Option<u64>,
+}
+
+impl Envelope {
+    pub fn text(id: &str, body: &str) -> Envelope {
+        Envelope {
+            version: PROTOCOL_VERSION,
+            id: id.to_string(),
+            kind: MessageKind::Text,
+            body: body.to_string(),
+            part: 0,
+            parts: 1,
+            reply_to: None,
+            amount: None,
+            asset: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn payment_request(
+        id: &str,
+        memo: &str,
+        amount: u64,
+        asset: String,
+        expires_at: Option<u64>,
+    ) -> Envelope {
+        Envelope {
+            kind: MessageKind::PaymentRequest,
+            amount: Some(amount),
+            asset: Some(asset),
+            expires_at,
+            ..Envelope::text(id, memo)
+        }
+    }
+
+    /// Payment of the request with the given reference
+    pub fn payment(id: &str, request_reference: String) -> Envelope {
+        Envelope {
+            kind: MessageKind::Payment,
+            reply_to: Some(request_reference),
+            ..Envelope::text(id, "")
+        }
+    }
+
+    // A bare string message
+    fn legacy(body: &str) -> Envelope {
+        Envelope {
+            version: 0,
+            ..Envelope::text("", body)
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    /// Whether the message is sent as a bare string, read by every client:
+    /// single part text without a reply
+    pub fn is_plain_text(&self) -> bool {
+        self.kind == MessageKind::Text
+            && self.reply_to.is_none()
+            && self.amount.is_none()
+            && self.asset.is_none()
+            && self.expires_at.is_none()
+            && parts_needed(&self.body) == 1
+    }
+
+    /// Id stored with the message, legacy messages have none
+    pub fn message_id(&self) -> Option<String> {
+        (!self.id.is_empty()).then(|| self.id.clone())
+    }
+
+    pub fn to_data_element(&self) -> DataElement {
+        let mut fields = IndexMap::new();
+        fields.insert(key(KEY_VERSION), value(DataValue::U8(self.version)));
+        fields.insert(key(KEY_ID), value(DataValue::String(self.id.clone())));
+        fields.insert(key(KEY_KIND), value(DataValue::String(self.kind.as_str().to_string())));
+        fields.insert(key(KEY_BODY), value(DataValue::String(self.body.clone())));
+
+        // single part messages leave the part fields out
+        if self.parts > 1 {
+            fields.insert(key(KEY_PART), value(DataValue::U8(self.part)));
+            fields.insert(key(KEY_PARTS), value(DataValue::U8(self.parts)));
+        }
+
+        if let Some(reply_to) = &self.reply_to {
+            fields.insert(key(KEY_REPLY_TO), value(DataValue::String(reply_to.clone())));
+        }
+
+        if let Some(amount) = self.amount {
+            fields.insert(key(KEY_AMOUNT), value(DataValue::U64(amount)));
+        }
+
+        if let Some(asset) = &self.asset {
+            fields.insert(key(KEY_ASSET), value(DataValue::String(asset.clone())));
+        }
+
+        if let Some(expires_at) = self.expires_at {
+            fields.insert(key(KEY_EXPIRES_AT), value(DataValue::U64(expires_at)));
+        }
+
+        DataElement::Fields(fields)
+    }
+
+    /// Reads an envelope, or a legacy string, from extra data.
+    /// Returns None for anything it does not understand, it never panics.
+    pub fn from_data_element(data: &DataElement) -> Option<Envelope> {
+        let fields = match data {
+            DataElement::Value(DataValue::String(text)) => return Some(Envelope::legacy(text)),
+            DataElement::Fields(fields) => fields,
+            _ => return None,
+        };
+
+        let version = get_u8(fields, KEY_VERSION)?;
+        if version == 0 {
+            return None;
+        }
+
+        let envelope = Envelope {
+            version,
+            id: get_string(fields, KEY_ID)?,
+            kind: MessageKind::parse(&get_string(fields, KEY_KIND)?)?,
+            body: get_string(fields, KEY_BODY)?,
+            part: get_optional_u8(fields, KEY_PART)?.unwrap_or(0),
+            parts: get_optional_u8(fields, KEY_PARTS)?.unwrap_or(1),
+            reply_to: get_optional_string(fields, KEY_REPLY_TO)?,
+            amount: get_optional_u64(fields, KEY_AMOUNT)?,
+            asset: get_optional_string(fields, KEY_ASSET)?,
+            expires_at: get_optional_u64(fields, KEY_EXPIRES_AT)?,
+        };
+
+        // ids end up in the page, only message ids and tx hashes are accepted
+        if !is_reference(&envelope.id)
+            || envelope
+                .reply_to
+                .as_deref()
+                .is_some_and(|reply_to| !is_reference(reply_to))
+        {
+            return None;
+        }
+
+        // a request without an amount cannot be paid
+        let is_valid_kind = match envelope.kind {
+            MessageKind::PaymentRequest => envelope.amount.is_some() && envelope.asset.is_some(),
+            MessageKind::Payment => envelope.reply_to.is_some(),
+            MessageKind::Text => true,
+        };
+
+        (is_valid_kind && envelope.part < envelope.parts).then_some(envelope)
+    }
+}
+
+/// Whether a message reference is a message id or a tx hash: hex digits, uuid dashes allowed
+pub fn is_reference(reference: &str) -> bool {
+    !reference.is_empty()
+        && reference.len() <= 64
+        && reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+fn key(name: &str) -> DataValue {
+    DataValue::String(name.to_string())
+}
+
+fn value(data: DataValue) -> DataElement {
+    DataElement::Value(data)
+}
+
+fn get_string(fields: &IndexMap<DataValue, DataElement>, name: &str) -> Option<String> {
+    match fields.get(&key(name)) {
+        Some(DataElement::Value(DataValue::String(text))) => Some(text.clone()),
+        _ => None,
+    }
+}
+
+fn get_optional_string(
+    fields: &IndexMap<DataValue, DataElement>,
+    name: &str,
+) -> Option<Option<String>> {
+    match fields.get(&key(name)) {
+        None => Some(None),
+        Some(DataElement::Value(DataValue::String(text))) => Some(Some(text.clone())),
+        Some(_) => None,
+    }
+}
+
+// other wallets may pick any integer size
+fn get_optional_u64(fields: &IndexMap<DataValue, DataElement>, name: &str) -> Option<Option<u64>> {
+    match fields.get(&key(name)) {
+        None => Some(None),
+        Some(DataElement::Value(DataValue::U8(number))) => Some(Some(*number as u64)),
+        Some(DataElement::Value(DataValue::U16(number))) => Some(Some(*number as u64)),
+        Some(DataElement::Value(DataValue::U32(number))) => Some(Some(*number as u64)),
+        Some(DataElement::Value(DataValue::U64(number))) => Some(Some(*number)),
+        Some(_) => None,
+    }
+}
+
+fn get_optional_u8(fields: &IndexMap<DataValue, DataElement>, name: &str) -> Option<Option<u8>> {
+    match get_optional_u64(fields, name)? {
+        Some(number) => u8::try_from(number).ok().map(Some),
+        None => Some(None),
+    }
+}
+
+fn get_u8(fields: &IndexMap<DataValue, DataElement>, name: &str) -> Option<u8> {
+    get_optional_u8(fields, name).flatten()
+}
+
+/// Splits a message in parts of at most `MESSAGE_PART_SIZE` bytes, on char boundaries
+pub fn split_message(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+
+    while rest.len() > MESSAGE_PART_SIZE {
+        let mut end = MESSAGE_PART_SIZE;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let (part, tail) = rest.split_at(end);
+        parts.push(part);
+        rest = tail;
+    }
+    parts.push(rest);
+
+    parts
+}
+
+/// Number of transfers a message needs
+pub fn parts_needed(text: &str) -> usize {
+    split_message(text).len()
+}
+
+/// Encodes a message as the extra data of one transfer per part,
+/// plain text stays a bare string
+pub fn encode_message(envelope: &Envelope) -> Result<Vec<DataElement>> {
+    // older clients only read bare strings
+    if envelope.is_plain_text() {
+        return Ok(vec![value(DataValue::String(envelope.body.clone()))]);
+    }
+
+    let parts = split_message(&envelope.body);
+
+    if parts.len() > MAX_MESSAGE_PARTS {
+        bail!(
+            "Message is too long: {} bytes, the limit is {} bytes",
+            envelope.body.len(),
+            MESSAGE_PART_SIZE * MAX_MESSAGE_PARTS
+        );
+    }
+
+    let total = parts.len() as u8;
+    Ok(parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, body)| {
+            Envelope {
+                body: body.to_string(),
+                part: i as u8,
+                parts: total,
+                ..envelope.clone()
+            }
+            .to_data_element()
+        })
+        .collect())
+}
+
+/// Decodes the messages carried by the transfers of a transaction.
+/// Parts are put back together, incomplete messages and unknown payloads are dropped.
+pub fn decode_messages<'a>(datas: impl IntoIterator<Item = &'a DataElement>) -> Vec<Envelope> {
+    decode_transfer_messages(datas.into_iter().enumerate())
+        .into_iter()
+        .map(|(_, envelope)| envelope)
+        .collect()
+}
+
+/// Decodes the messages carried by the indexed transfers of a transaction, each one with the
+/// index of the transfer carrying its first part, in transfer order.
+pub fn decode_transfer_messages<'a>(
+    datas: impl IntoIterator<Item = (usize, &'a DataElement)>,
+) -> Vec<(usize, Envelope)> {
+    let mut messages = Vec::new();
+    let mut chunked: HashMap<String, Vec<(usize, Envelope)>> = HashMap::new();
+
+    for (index, data) in datas {
+        let Some(envelope) = Envelope::from_data_element(data) else {
+            continue;
+        };
+
+        if envelope.is_legacy() || envelope.parts == 1 {
+            messages.push((index, envelope));
+        } else {
+            chunked
+                .entry(envelope.id.clone())
+                .or_default()
+                .push((index, envelope));
+        }
+    }
+
+    for (_, mut parts) in chunked {
+        parts.sort_by_key(|(_, part)| part.part);
+        parts.dedup_by_key(|(_, part)| part.part);
+
+        let (index, first) = parts[0].clone();
+        let total = first.parts as usize;
+        let is_complete = parts.len() == total
+            && parts.iter().enumerate().all(|(i, (_, part))| {
+                part.part as usize == i && part.parts == first.parts && part.kind == first.kind
+            });
+
+        if is_complete {
+            messages.push((
+                index,
+                Envelope {
+                    body: parts.into_iter().map(|(_, part)| part.body).collect(),
+                    part: 0,
+                    parts: 1,
+                    ..first
+                },
+            ));
+        }
+    }
+
+    messages.sort_by_key(|(index, _)| *index);
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef";
+
+    // a valid text envelope with some fields replaced, or removed when None
+    fn fields(changes: Vec<(&str, Option<DataValue>)>) -> DataElement {
+        let DataElement::Fields(mut fields) = Envelope::text(ID, "hi").to_data_element() else {
+            unreachable!()
+        };
+
+        for (name, data) in changes {
+            match data {
+                Some(data) => fields.insert(key(name), value(data)),
+                None => fields.shift_remove(&key(name)),
+            };
+        }
+
+        DataElement::Fields(fields)
+    }
+
+    fn string(text: &str) -> DataValue {
+        DataValue::String(text.to_string())
+    }
+
+    fn data_value() -> impl Strategy<Value = DataValue> {
+        prop_oneof![
+            any::<bool>().prop_map(DataValue::Bool),
+            ".{0,40}".prop_map(DataValue::String),
+            "[0-9a-f-]{0,70}".prop_map(DataValue::String),
+            prop_oneof![Just("text"), Just("payment_request"), Just("payment")]
+                .prop_map(|kind| DataValue::String(kind.to_string())),
+            any::<u8>().prop_map(DataValue::U8),
+            any::<u16>().prop_map(DataValue::U16),
+            any::<u32>().prop_map(DataValue::U32),
+            any::<u64>().prop_map(DataValue::U64),
+        ]
+    }
+
+    // envelope keys most of the time, so payloads get close to valid ones
+    fn field_key() -> impl Strategy<Value = DataValue> {
+        prop_oneof![
+            prop::sample::select(vec![
+                KEY_VERSION,
+                KEY_ID,
+                KEY_KIND,
+                KEY_BODY,
+                KEY_PART,
+                KEY_PARTS,
+                KEY_REPLY_TO,
+                KEY_AMOUNT,
+                KEY_ASSET,
+                KEY_EXPIRES_AT,
+            ])
+            .prop_map(key),
+            data_value(),
+        ]
+    }
+
+    fn data_element() -> impl Strategy<Value = DataElement> {
+        data_value()
+            .prop_map(DataElement::Value)
+            .prop_recursive(3, 64, 10, |inner| {
+                prop_oneof![
+                    prop::collection::vec(inner.clone(), 0..6).prop_map(DataElement::Array),
+                    prop::collection::vec((field_key(), inner), 0..10)
+                        .prop_map(|entries| DataElement::Fields(entries.into_iter().collect())),
+                ]
+            })
+    }
+
+    #[test]
+    fn malformed_payloads_are_dropped() {
+        let malformed = vec![
+            value(DataValue::U64(7)),
+            value(DataValue::Bool(true)),
+            DataElement::Array(vec![value(string("hi"))]),
+            DataElement::Fields(IndexMap::new()),
+            // version 0 is reserved for bare strings
+            fields(vec![(KEY_VERSION, Some(DataValue::U8(0)))]),
+            fields(vec![(KEY_VERSION, Some(DataValue::U64(300)))]),
+            fields(vec![(KEY_VERSION, Some(string("1")))]),
+            fields(vec![(KEY_BODY, None)]),
+            fields(vec![(KEY_BODY, Some(DataValue::U8(1)))]),
+            fields(vec![(KEY_KIND, Some(string("poll")))]),
+            fields(vec![(KEY_ID, None)]),
+            fields(vec![(KEY_ID, Some(string("")))]),
+            fields(vec![(KEY_ID, Some(string(&"a".repeat(65))))]),
+            // ids and replies end up in the page
+            fields(vec![(KEY_ID, Some(string("')+alert(1)+('")))]),
+            fields(vec![(KEY_REPLY_TO, Some(string("');alert(1);('")))]),
+            fields(vec![(KEY_REPLY_TO, Some(DataValue::U64(1)))]),
+            fields(vec![(KEY_AMOUNT, Some(string("1")))]),
+            fields(vec![
+                (KEY_PART, Some(DataValue::U8(2))),
+                (KEY_PARTS, Some(DataValue::U8(2))),
+            ]),
+            fields(vec![(KEY_PARTS, Some(DataValue::U8(0)))]),
+            fields(vec![(KEY_PARTS, Some(DataValue::U16(256)))]),
+            // a request without an amount, a payment answering nothing
+            fields(vec![
+                (KEY_KIND, Some(string("payment_request"))),
+                (KEY_ASSET, Some(string(ID))),
+            ]),
+            fields(vec![(KEY_KIND, Some(string("payment")))]),
+        ];
+
+        for data in &malformed {
+            assert_eq!(Envelope::from_data_element(data), None, "{data:?}");
+        }
+        assert!(decode_messages(&malformed).is_empty());
+    }
+
+    #[test]
+    fn bare_strings_are_legacy_text() {
+        let envelope = Envelope::from_data_element(&value(string("hello"))).unwrap();
+
+        assert!(envelope.is_legacy());
+        assert_eq!(envelope.body, "hello");
+        assert_eq!(envelope.message_id(), None);
+    }
+
+    #[test]
+    fn plain_text_is_sent_as_a_bare_string() {
+        let encoded = encode_message(&Envelope::text(ID, "hello")).unwrap();
+        assert_eq!(encoded, vec![value(string("hello"))]);
+
+        // anything else needs the envelope fields
+        let reply = Envelope {
+            reply_to: Some(ID.to_string()),
+            ..Envelope::text(ID, "hello")
+        };
+        assert!(matches!(
+            encode_message(&reply).unwrap()[..],
+            [DataElement::Fields(_)]
+        ));
+    }
+
+    #[test]
+    fn incomplete_messages_are_dropped() {
+        let body = "x".repeat(MESSAGE_PART_SIZE * 3);
+        let mut encoded = encode_message(&Envelope::text(ID, &body)).unwrap();
+        encoded.remove(1);
+
+        assert!(decode_messages(&encoded).is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_payloads_never_panic(datas in prop::collection::vec(data_element(), 0..8)) {
+            for data in &datas {
+                let _ = Envelope::from_data_element(data);
+            }
+            let _ = decode_messages(&datas);
+        }
+
+        #[test]
+        fn messages_survive_encoding(
+            body in ".{0,3000}",
+            reply_to in proptest::option::of("[0-9a-f]{64}"),
+            shuffle in any::<prop::sample::Index>(),
+        ) {
+            let envelope = Envelope {
+                reply_to,
+                ..Envelope::text(ID, &body)
+            };
+            let mut encoded = encode_message(&envelope).unwrap();
+
+            // transfers can come in any order
+            let len = encoded.len();
+            encoded.rotate_left(shuffle.index(len));
+
+            let decoded = decode_messages(&encoded);
+            prop_assert_eq!(decoded.len(), 1);
+            prop_assert_eq!(&decoded[0].body, &body);
+            prop_assert_eq!(&decoded[0].reply_to, &envelope.reply_to);
+        }
+    }
+}
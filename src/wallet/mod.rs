@@ -1,2 +1,4 @@
+pub mod events;
+pub mod message;
 pub mod utils;
 pub mod wallet_fns;
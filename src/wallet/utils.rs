@@ -6,11 +6,12 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 use xelis_common::{
     api::{
         wallet::{BaseFeeMode, EntryType, TransactionEntry},
-        DataElement, DataValue,
+        DataElement,
     },
     config::{COIN_DECIMALS, XELIS_ASSET},
     crypto::{Address, Hash, Hashable},
@@ -19,10 +20,11 @@ use xelis_common::{
         builder::{FeeBuilder, TransactionTypeBuilder, TransferBuilder},
         Transaction,
     },
-    utils::{format_coin, format_xelis},
+    utils::{format_coin, format_xelis, sanitize_daemon_address},
 };
 use xelis_wallet::{
     config::LogProgressTableGenerationReportFunction,
+    daemon_api::DaemonAPI,
     entry::EntryData,
     precomputed_tables::{self, L1_FULL, L1_LOW, L1_MEDIUM},
     transaction_builder::TransactionBuilderState,
@@ -31,35 +33,191 @@ use xelis_wallet::{
 
 pub use xelis_common::network::Network;
 
-use crate::{
-    database::db_fns::{db_update_status_fee, db_update_status_topoheight},
-    views::DbMessage,
-    IS_READY,
+use crate::views::{DbMessage, MessageDirection, MessageStatus};
+
+use super::{
+    events::AppEvent,
+    message::{decode_messages, decode_transfer_messages},
 };
 
-// pub const NODE_ENDPOINT: &str = "node.xelis.io"; //mainnet
-// pub static NETWORK: Network = Network::Mainnet; //mainnet
+// Network the wallet is created / restored on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalletNetwork {
+    Mainnet,
+    #[default]
+    Testnet,
+    Devnet,
+}
+
+impl WalletNetwork {
+    pub const ALL: [WalletNetwork; 3] = [
+        WalletNetwork::Mainnet,
+        WalletNetwork::Testnet,
+        WalletNetwork::Devnet,
+    ];
+
+    pub fn convert(&self) -> Network {
+        match self {
+            WalletNetwork::Mainnet => Network::Mainnet,
+            WalletNetwork::Testnet => Network::Testnet,
+            WalletNetwork::Devnet => Network::Devnet,
+        }
+    }
+
+    /// Default node used when the wallet goes online
+    pub fn node_endpoint(&self) -> &'static str {
+        self.default_nodes()[0]
+    }
+
+    /// Built-in nodes of the network, always part of the node registry
+    pub fn default_nodes(&self) -> &'static [&'static str] {
+        match self {
+            WalletNetwork::Mainnet => &["node.xelis.io"],
+            WalletNetwork::Testnet => &["testnet-node.xelis.io"],
+            WalletNetwork::Devnet => &["127.0.0.1:8080"],
+        }
+    }
+
+    /// Name used to persist the network in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletNetwork::Mainnet => "mainnet",
+            WalletNetwork::Testnet => "testnet",
+            WalletNetwork::Devnet => "devnet",
+        }
+    }
+}
+
+impl std::fmt::Display for WalletNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for WalletNetwork {
+    type Err = anyhow::Error;
+
+    fn from_str(network: &str) -> Result<Self> {
+        match network.to_lowercase().as_str() {
+            "mainnet" => Ok(WalletNetwork::Mainnet),
+            "testnet" => Ok(WalletNetwork::Testnet),
+            "devnet" => Ok(WalletNetwork::Devnet),
+            _ => Err(anyhow!("Unknown network: {network}")),
+        }
+    }
+}
 
-pub const NODE_ENDPOINT: &str = "testnet-node.xelis.io"; //testnet
-pub static NETWORK: Network = Network::Testnet; //testnet
+// Max time a node has to answer a health probe
+const NODE_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Result of a node health probe
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeHealth {
+    pub endpoint: String,
+    pub latency_ms: u64,
+    pub version: String,
+    pub topoheight: u64,
+    pub is_healthy: bool,
+    pub error: Option<String>,
+}
+
+/// Probes a node with `get_info` and measures its latency
+pub async fn probe_node(endpoint: String, network: WalletNetwork) -> NodeHealth {
+    let mut health = NodeHealth {
+        endpoint: endpoint.clone(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let probe = async {
+        let api = DaemonAPI::new(format!("{}/json_rpc", sanitize_daemon_address(&endpoint))).await?;
+        api.get_info().await
+    };
+
+    match tokio::time::timeout(NODE_PROBE_TIMEOUT, probe).await {
+        Ok(Ok(info)) => {
+            health.latency_ms = start.elapsed().as_millis() as u64;
+            health.version = info.version;
+            health.topoheight = info.topoheight;
+
+            if info.network == network.convert() {
+                health.is_healthy = true;
+            } else {
+                health.error = Some(format!("Node runs on {}, not on {network}", info.network));
+            }
+        }
+        Ok(Err(e)) => health.error = Some(e.to_string()),
+        Err(_) => health.error = Some("Node did not answer in time".to_string()),
+    }
+
+    health
+}
 
-// pub const NODE_ENDPOINT: &str = "192.168.100.7:8080"; // local
+/// Probes the nodes together, the healthy ones from the fastest
+pub async fn rank_nodes(endpoints: Vec<String>, network: WalletNetwork) -> Vec<NodeHealth> {
+    let mut healths = futures::future::join_all(
+        endpoints
+            .into_iter()
+            .map(|endpoint| probe_node(endpoint, network)),
+    )
+    .await;
+
+    healths.retain(|health| health.is_healthy);
+    healths.sort_by_key(|health| health.latency_ms);
+
+    healths
+}
+
+// Amount attached to a chat message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payment {
+    // atomic units of the asset
+    pub amount: u64,
+    pub asset_hash: String,
+}
+
+// Asset known to the wallet storage
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub hash: String,
+    pub name: String,
+    pub ticker: String,
+    pub decimals: u8,
+}
+
+impl AssetInfo {
+    /// Formats atomic units with the asset decimals and ticker
+    pub fn format_amount(&self, atomic_amount: u64) -> String {
+        format!("{} {}", format_coin(atomic_amount, self.decimals), self.ticker)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AssetBalance {
+    pub asset: AssetInfo,
+    pub balance: u64,
+}
 
 #[derive(Clone, Debug)]
 pub struct Transfer {
-    pub float_amount: f64,
+    // atomic units of the asset
+    pub amount: u64,
     pub str_address: String,
     pub asset_hash: String,
-    pub extra_data: Option<String>,
+    pub extra_data: Option<DataElement>,
 }
 // ECDLP Tables L1 size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TableSize {
+    #[default]
     L1Low,
     L1Medium,
     L1Full,
 }
 
 impl TableSize {
+    pub const ALL: [TableSize; 3] = [TableSize::L1Low, TableSize::L1Medium, TableSize::L1Full];
+
     pub fn convert(&self) -> usize {
         match self {
             TableSize::L1Low => L1_LOW,
@@ -69,7 +227,9 @@ impl TableSize {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MnemonicLanguage {
+    #[default]
     English,
     French,
     Italian,
@@ -84,6 +244,20 @@ pub enum MnemonicLanguage {
 }
 
 impl MnemonicLanguage {
+    pub const ALL: [MnemonicLanguage; 11] = [
+        MnemonicLanguage::English,
+        MnemonicLanguage::French,
+        MnemonicLanguage::Italian,
+        MnemonicLanguage::Spanish,
+        MnemonicLanguage::Portuguese,
+        MnemonicLanguage::Japanese,
+        MnemonicLanguage::ChineseSimplified,
+        MnemonicLanguage::Russian,
+        MnemonicLanguage::Esperanto,
+        MnemonicLanguage::Dutch,
+        MnemonicLanguage::German,
+    ];
+
     pub fn convert(&self) -> usize {
         match self {
             MnemonicLanguage::English => 0,
@@ -101,6 +275,16 @@ impl MnemonicLanguage {
     }
 }
 
+// State of a broadcast transaction, checked when the wallet opens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SentTxState {
+    Confirmed { topoheight: u64, timestamp: u64 },
+    // in mempool or not yet synced by the wallet
+    Unconfirmed,
+    // never made it on-chain
+    Dropped,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SummaryTransaction {
     pub hash: String,
@@ -108,14 +292,195 @@ pub struct SummaryTransaction {
     transaction_type: TransactionTypeBuilder,
 }
 
-pub struct ChatWallet {
-    wallet: Arc<Wallet>,
-    pub rx_messages: Vec<DbMessage>,
+// Chat state kept up to date by the wallet events
+#[derive(Clone, Debug, Default)]
+pub struct WalletState {
     pub balance: String,
+    // atomic balance of every asset, by asset hash
+    pub balances: HashMap<String, u64>,
     pub topoheight: i64,
     pub is_online: bool,
+    pub history_synced: bool,
     pub sent_tx_hashes: Vec<String>,
-    pub confirmed_messages: Vec<DbMessage>,
+}
+
+// Messages carried by an incoming transaction, one per envelope
+fn process_incoming_tx(transaction: TransactionEntry) -> Vec<DbMessage> {
+    let rx_message = DbMessage {
+        status: MessageStatus::Confirmed,
+        direction: MessageDirection::Incoming,
+        address: Default::default(),
+        hash: transaction.hash.to_string(),
+        fee: Default::default(),
+        timestamp: transaction.timestamp as i64,
+        topoheight: transaction.topoheight as i64,
+        asset: Default::default(),
+        amount: Default::default(),
+        message: Default::default(),
+        ..Default::default()
+    };
+
+    let entry_data = transaction.entry;
+
+    let mut rx_messages = Vec::new();
+
+    if let EntryType::Incoming { from, transfers } = entry_data {
+        // every message of the transaction is stored apart, a batch carries several
+        let datas = transfers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, transfer_in)| {
+                transfer_in
+                    .extra_data
+                    .as_ref()
+                    .and_then(|extra_data| extra_data.data())
+                    .map(|data| (index, data))
+            });
+
+        for (index, envelope) in decode_transfer_messages(datas) {
+            // the payment sent with a message is on the transfer of its first part
+            let transfer_in = &transfers[index];
+
+            let mut message = DbMessage {
+                local_id: DbMessage::new_local_id(),
+                address: from.as_string().unwrap(),
+                asset: transfer_in.asset.to_string(),
+                amount: transfer_in.amount as i64,
+                transfer_index: index as i64,
+                ..rx_message.clone()
+            };
+            message.set_envelope(envelope);
+
+            rx_messages.push(message);
+        }
+    }
+
+    rx_messages
+}
+
+impl WalletState {
+    /// Applies a wallet event to the chat state, returns the app events to publish
+    pub fn handle_event(&mut self, event: Event) -> Vec<AppEvent> {
+        match event {
+            Event::NewTransaction(transaction) => {
+                info!("NewTransaction");
+
+                let confirmed_message = DbMessage {
+                    status: MessageStatus::Confirmed,
+                    hash: transaction.hash.to_string(),
+                    timestamp: transaction.timestamp as i64,
+                    topoheight: transaction.topoheight as i64,
+                    ..Default::default()
+                };
+
+                let rx_messages = process_incoming_tx(transaction);
+
+                // only tranactions with messages are stored
+                if !rx_messages.is_empty() {
+                    rx_messages
+                        .into_iter()
+                        .map(AppEvent::MessageReceived)
+                        .collect()
+                } else if let Some(index) = self
+                    .sent_tx_hashes
+                    .iter()
+                    .position(|hash| *hash == confirmed_message.hash)
+                {
+                    let _ = self.sent_tx_hashes.remove(index);
+
+                    // confirms every message sent with the transaction
+                    vec![AppEvent::MessageConfirmed(confirmed_message)]
+                } else {
+                    // only balance has changed
+                    Vec::new()
+                }
+            }
+            Event::NewTopoHeight { topoheight } => {
+                info!("NewTopoHeight: {topoheight}");
+                self.topoheight = topoheight as i64;
+                vec![AppEvent::NewTopoHeight(self.topoheight)]
+            }
+            Event::HistorySynced { topoheight } => {
+                info!("HistorySynced: {topoheight}");
+                self.history_synced = true;
+                vec![AppEvent::HistorySynced]
+            }
+            Event::BalanceChanged(new_balance) => {
+                info!("BalanceChanged: {new_balance:?}");
+                self.balances
+                    .insert(new_balance.asset.to_hex(), new_balance.balance);
+
+                if new_balance.asset == XELIS_ASSET {
+                    self.balance = format_xelis(new_balance.balance);
+                    vec![AppEvent::BalanceChanged(self.balance.clone())]
+                } else {
+                    Vec::new()
+                }
+            }
+            Event::NewAsset(_) => {
+                info!("NewAsset");
+                vec![AppEvent::NewAsset]
+            }
+            Event::Online => {
+                info!("Online");
+                self.is_online = true;
+                vec![AppEvent::Online]
+            }
+            Event::Offline => {
+                info!("Offline");
+                self.is_online = false;
+                vec![AppEvent::Offline]
+            }
+            Event::Rescan { start_topoheight } => {
+                info!("Rescan: {start_topoheight}");
+                // the history is synced again, transactions above are stored again
+                self.history_synced = false;
+                vec![AppEvent::Rescan(start_topoheight)]
+            }
+            Event::SyncError { message } => {
+                info!("SyncError: {message}");
+                vec![AppEvent::SyncError(message)]
+            }
+            Event::TransactionOrphaned(transaction) => {
+                info!("TransactionOrphaned: {}", transaction.hash);
+                self.orphan_transaction(transaction)
+            }
+            Event::TransactionRemoved(transaction) => {
+                info!("TransactionRemoved: {}", transaction.hash);
+                self.orphan_transaction(transaction)
+            }
+        }
+    }
+
+    // The messages of a transaction out of the chain are orphaned until it is included again,
+    // a sent one is confirmed again by its next NewTransaction
+    fn orphan_transaction(&mut self, transaction: TransactionEntry) -> Vec<AppEvent> {
+        let hash = transaction.hash.to_string();
+
+        if matches!(transaction.entry, EntryType::Outgoing { .. })
+            && !self.sent_tx_hashes.contains(&hash)
+        {
+            self.sent_tx_hashes.push(hash.clone());
+        }
+
+        vec![AppEvent::MessageOrphaned(DbMessage {
+            status: MessageStatus::Orphaned,
+            hash,
+            timestamp: transaction.timestamp as i64,
+            topoheight: transaction.topoheight as i64,
+            ..Default::default()
+        })]
+    }
+}
+
+pub struct ChatWallet {
+    wallet: Arc<Wallet>,
+    pub network: WalletNetwork,
+    pub active_node: Option<NodeHealth>,
+    pub state: WalletState,
+    // unix timestamp the wallet was opened at, messages built since belong to this session
+    pub opened_at: i64,
+    pub pending_reconciled: bool,
     pub pending_transactions: Arc<RwLock<HashMap<Hash, (Transaction, TransactionBuilderState)>>>,
 }
 
@@ -126,11 +491,12 @@ impl ChatWallet {
     pub async fn create_wallet(
         name: String,
         password: String,
-        network: Network,
+        network: WalletNetwork,
         seed: Option<String>,
         private_key: Option<String>,
         precomputed_tables_path: Option<String>,
         precomputed_table_size: Option<TableSize>,
+        n_threads: usize,
     ) -> Result<ChatWallet> {
         // recover wallet by seed or private_key
         let seed: Option<RecoverOption> = if let Some(seed) = seed.as_deref() {
@@ -155,14 +521,12 @@ impl ChatWallet {
         )
         .await?;
 
-        let n_threads = 4; // hardcoding to 4 for debug purposes
-
         // create a wallet instance
         let chat_wallet = Wallet::create(
             &name,
             &password,
             seed,
-            network,
+            network.convert(),
             precomputed_tables,
             n_threads,
             n_threads,
@@ -171,12 +535,11 @@ impl ChatWallet {
 
         Ok(ChatWallet {
             wallet: chat_wallet,
-            rx_messages: Vec::new(),
-            balance: String::new(),
-            topoheight: 0,
-            is_online: false,
-            sent_tx_hashes: Vec::new(),
-            confirmed_messages: Vec::new(),
+            network,
+            active_node: None,
+            state: WalletState::default(),
+            opened_at: Utc::now().timestamp(),
+            pending_reconciled: false,
             pending_transactions: Arc::new(RwLock::new(HashMap::new())),
         })
     }
@@ -185,9 +548,10 @@ impl ChatWallet {
     pub async fn open_wallet(
         name: String,
         password: String,
-        network: Network,
+        network: WalletNetwork,
         precomputed_tables_path: Option<String>,
         precomputed_table_size: Option<TableSize>,
+        n_threads: usize,
     ) -> Result<ChatWallet> {
         // get the size conversion or default
         let precomputed_table_size = if let Some(precomputed_tables_size) = precomputed_table_size {
@@ -205,109 +569,39 @@ impl ChatWallet {
         )
         .await?;
 
-        let n_threads = 4; // hardcoding rn
-
         let chat_wallet = Wallet::open(
             name.as_str(),
             password.as_str(),
-            network,
+            network.convert(),
             precomputed_tables,
             n_threads,
             n_threads,
-        )?;
+        )
+        .with_context(|| format!("Cannot open wallet '{name}' on {network}"))?;
+
+        // make sure the wallet storage belongs to the requested network
+        if *chat_wallet.get_network() != network.convert() {
+            chat_wallet.close().await;
+            bail!(
+                "Wallet '{name}' was created on {}, not on {network}",
+                chat_wallet.get_network()
+            );
+        }
 
         Ok(ChatWallet {
             wallet: chat_wallet,
-            rx_messages: Vec::new(),
-            balance: String::new(),
-            topoheight: 0,
-            is_online: false,
-            sent_tx_hashes: Vec::new(),
-            confirmed_messages: Vec::new(),
+            network,
+            active_node: None,
+            state: WalletState::default(),
+            opened_at: Utc::now().timestamp(),
+            pending_reconciled: false,
             pending_transactions: Arc::new(RwLock::new(HashMap::new())),
         })
     }
 
-    async fn process_incoming_tx(&self, transaction: TransactionEntry) -> DbMessage {
-        let mut rx_message = DbMessage {
-            status: "Received".to_string(),
-            direction: "Incoming".to_string(),
-            address: Default::default(),
-            hash: transaction.hash.to_string(),
-            fee: Default::default(),
-            timestamp: transaction.timestamp as i64,
-            topoheight: transaction.topoheight as i64,
-            asset: Default::default(),
-            amount: Default::default(),
-            message: Default::default(),
-        };
-
-        let entry_data = transaction.entry;
-
-        if let EntryType::Incoming { from, transfers } = entry_data {
-            rx_message.address = from.as_string().unwrap();
-
-            for transfer_in in transfers.iter() {
-                rx_message.asset = transfer_in.asset.to_string();
-                rx_message.amount = transfer_in.amount as i64;
-
-                rx_message.message = match transfer_in.extra_data.clone() {
-                    Some(extra_data) => extra_data.data().map(|extra_data| {
-                        extra_data.clone().to_value().unwrap().to_string().unwrap()
-                    }),
-                    None => None,
-                };
-            }
-        }
-
-        rx_message
-    }
-
-    #[allow(clippy::await_holding_invalid_type)]
-    pub async fn backgroud_daemon(&mut self) {
-        let wallet = self.get_wallet().await;
-        let mut receiver = wallet.subscribe_events().await;
-
-        if let Ok(event) = receiver.recv().await {
-            match event {
-                Event::NewTransaction(transaction) => {
-                    info!("NewTransaction");
-
-                    let mut processed_tx = self.process_incoming_tx(transaction.clone()).await;
-
-                    // only store tranactions with messages
-                    if processed_tx.message.is_some() {
-                        self.rx_messages.push(processed_tx);
-                    } else if let Some(index) = self
-                        .sent_tx_hashes
-                        .iter()
-                        .position(|hash| *hash == processed_tx.hash)
-                    {
-                        let _ = self.sent_tx_hashes.remove(index);
-
-                        processed_tx.status = "Received".to_string();
-
-                        self.confirmed_messages.push(processed_tx);
-                    } else {
-
-                        // only balance has changed
-                    }
-
-                    info!("WalletTransactions: {:#?}", self.rx_messages);
-                }
-                Event::NewTopoHeight { topoheight } => {
-                    info!("NewTopoHeight: {topoheight}");
-                    self.topoheight = topoheight as i64;
-                }
-                Event::BalanceChanged(new_balance) => {
-                    info!("BalanceChanged: {new_balance:?}");
-                    if new_balance.asset == XELIS_ASSET {
-                        self.balance = format_xelis(new_balance.balance);
-                    }
-                }
-                _ => {}
-            }
-        }
+    /// Applies a wallet event to the chat state, returns the app events to publish
+    pub async fn handle_event(&mut self, event: Event) -> Vec<AppEvent> {
+        self.state.handle_event(event)
     }
 
     /// Get wallet txs
@@ -341,14 +635,13 @@ impl ChatWallet {
             for tx in txs.iter() {
                 if let EntryData::Incoming { from, transfers } = tx.get_entry().clone() {
                     if from.to_address(false).to_string() == contact_address {
-                        for transfer in transfers {
-                            if let Some(extra_data) = transfer.get_extra_data() {
-                                if let Some(DataElement::Value(data_element)) = extra_data.data() {
-                                    if let Ok(data) = data_element.as_string() {
-                                        data_vec.push(data.clone());
-                                    }
-                                }
-                            }
+                        for envelope in decode_messages(
+                            transfers
+                                .iter()
+                                .filter_map(|transfer| transfer.get_extra_data().as_ref())
+                                .filter_map(|extra_data| extra_data.data()),
+                        ) {
+                            data_vec.push(envelope.body);
                         }
                     }
                 }
@@ -360,6 +653,114 @@ impl ChatWallet {
         }
     }
 
+    /// Rebuilds the chat messages from the transactions in wallet storage
+    pub async fn get_history_messages(&self) -> Result<Vec<DbMessage>> {
+        let storage = self.wallet.get_storage().read().await;
+        let is_mainnet = self.network.convert().is_mainnet();
+
+        let txs = storage.get_filtered_transactions(
+            None, None, None, None, true, true, false, false, None, None, None,
+        )?;
+
+        let mut messages = Vec::new();
+
+        for tx in txs.iter() {
+            let mut message = DbMessage {
+                hash: tx.get_hash().to_hex(),
+                timestamp: tx.get_timestamp() as i64,
+                topoheight: tx.get_topoheight() as i64,
+                ..Default::default()
+            };
+
+            match tx.get_entry() {
+                EntryData::Incoming { from, transfers } => {
+                    message.status = MessageStatus::Confirmed;
+                    message.direction = MessageDirection::Incoming;
+                    message.address = from.clone().to_address(is_mainnet).to_string();
+
+                    let datas = transfers
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(index, transfer)| {
+                            transfer
+                                .get_extra_data()
+                                .as_ref()
+                                .and_then(|extra_data| extra_data.data())
+                                .map(|data| (index, data))
+                        });
+
+                    for (index, envelope) in decode_transfer_messages(datas) {
+                        // the payment sent with a message is on the transfer of its first part
+                        let transfer = &transfers[index];
+
+                        let mut rx_message = DbMessage {
+                            local_id: DbMessage::new_local_id(),
+                            asset: transfer.get_asset().to_string(),
+                            amount: transfer.get_amount() as i64,
+                            transfer_index: index as i64,
+                            ..message.clone()
+                        };
+                        rx_message.set_envelope(envelope);
+
+                        messages.push(rx_message);
+                    }
+                }
+                EntryData::Outgoing { transfers, fee, .. } => {
+                    // history transactions are in a block, they become final with the next topoheight
+                    message.status = MessageStatus::Confirmed;
+                    message.direction = MessageDirection::Outgoing;
+                    message.fee = format_xelis(*fee).parse::<f64>().unwrap_or_default();
+
+                    // group the transfers by destination so the parts of a message stay together,
+                    // transfers without a message, like the dev fee, are skipped
+                    let mut destinations: Vec<(String, Vec<_>)> = Vec::new();
+                    for (index, transfer) in transfers.iter().enumerate() {
+                        let address = transfer
+                            .get_destination()
+                            .clone()
+                            .to_address(is_mainnet)
+                            .to_string();
+
+                        match destinations.iter_mut().find(|(dest, _)| *dest == address) {
+                            Some((_, group)) => group.push((index, transfer)),
+                            None => destinations.push((address, vec![(index, transfer)])),
+                        }
+                    }
+
+                    for (address, group) in destinations {
+                        let datas = group.iter().filter_map(|(index, transfer)| {
+                            transfer
+                                .get_extra_data()
+                                .as_ref()
+                                .and_then(|extra_data| extra_data.data())
+                                .map(|data| (*index, data))
+                        });
+
+                        for (index, envelope) in decode_transfer_messages(datas) {
+                            // the payment sent with a message is on the transfer of its first part
+                            let payment = &transfers[index];
+
+                            let mut tx_message = DbMessage {
+                                local_id: DbMessage::new_local_id(),
+                                address: address.clone(),
+                                asset: payment.get_asset().to_string(),
+                                amount: payment.get_amount() as i64,
+                                transfer_index: index as i64,
+                                ..message.clone()
+                            };
+                            tx_message.set_envelope(envelope);
+
+                            messages.push(tx_message);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(messages)
+    }
+
     /// Change wallet password
     pub async fn change_password(&self, old_password: String, new_password: String) -> Result<()> {
         self.wallet.set_password(&old_password, &new_password).await
@@ -370,6 +771,27 @@ impl ChatWallet {
         Ok(self.wallet.set_online_mode(&daemon_address, true).await?)
     }
 
+    /// Sets the wallet online on the first node that accepts it, in the `rank_nodes` order
+    pub async fn connect_node(&mut self, healths: Vec<NodeHealth>) -> Result<NodeHealth> {
+        if self.is_online().await {
+            self.set_offline().await?;
+        }
+        self.active_node = None;
+
+        for health in healths {
+            match self.set_online(health.endpoint.clone()).await {
+                Ok(_) => {
+                    info!("Connected to node {}", health.endpoint);
+                    self.active_node = Some(health.clone());
+                    return Ok(health);
+                }
+                Err(e) => warn!("Cannot connect to node {}: {e}", health.endpoint),
+            }
+        }
+
+        bail!("No healthy node available on {}", self.network)
+    }
+
     // Set the wallet to offline mode
     pub async fn set_offline(&self) -> Result<()> {
         self.wallet.set_offline_mode().await?;
@@ -418,7 +840,7 @@ impl ChatWallet {
         let storage = self.wallet.get_storage().read().await;
         let balance = storage.get_plaintext_balance_for(&XELIS_ASSET).await?;
         let formatted_balance = format_xelis(balance);
-        self.balance = formatted_balance.clone();
+        self.state.balance = formatted_balance.clone();
 
         Ok(formatted_balance)
     }
@@ -447,6 +869,60 @@ impl ChatWallet {
         Ok(format_coin(atomic_amount, decimals))
     }
 
+    /// Lists the assets known to the wallet, XELIS first
+    pub async fn get_assets(&self) -> Result<Vec<AssetInfo>> {
+        let storage = self.wallet.get_storage().read().await;
+
+        let mut assets = Vec::new();
+        for hash in storage.get_assets().await? {
+            let data = storage.get_asset(&hash).await?;
+
+            assets.push(AssetInfo {
+                hash: hash.to_hex(),
+                name: data.get_name().to_string(),
+                ticker: data.get_ticker().to_string(),
+                decimals: data.get_decimals(),
+            });
+        }
+
+        let xelis = XELIS_ASSET.to_hex();
+        assets.sort_by_key(|asset| (asset.hash != xelis, asset.ticker.clone()));
+
+        Ok(assets)
+    }
+
+    /// Reads the balance of every asset known to the wallet
+    pub async fn get_balances(&mut self) -> Result<Vec<AssetBalance>> {
+        let assets = self.get_assets().await?;
+
+        let mut balances = Vec::new();
+        {
+            let storage = self.wallet.get_storage().read().await;
+
+            for asset in assets {
+                let hash = Hash::from_hex(&asset.hash).context("Invalid asset")?;
+                // assets never received have no balance yet
+                let balance = storage.get_plaintext_balance_for(&hash).await.unwrap_or(0);
+
+                balances.push(AssetBalance { asset, balance });
+            }
+        }
+
+        for balance in balances.iter() {
+            self.state
+                .balances
+                .insert(balance.asset.hash.clone(), balance.balance);
+        }
+
+        Ok(balances)
+    }
+
+    /// Converts a float amount of an asset to atomic units
+    pub async fn atomic_amount(&self, float_amount: f64, asset_hash: &str) -> Result<u64> {
+        let asset = Hash::from_hex(asset_hash).context("Invalid asset")?;
+        self.convert_float_amount(float_amount, &asset).await
+    }
+
     /// Estimates the fees for a transaction
     pub async fn estimate_fees(&self, transfers: Vec<Transfer>) -> Result<String> {
         let transaction_builder = self
@@ -472,9 +948,7 @@ impl ChatWallet {
         &mut self,
         transfers: Vec<Transfer>,
     ) -> Result<SummaryTransaction> {
-        // clear the pending transacitons
-        self.pending_transactions.write().unwrap().clear();
-
+        // other built transactions stay pending, each one is broadcast in turn
         info!("Building Transaction...");
 
         let transaction_type_builder = self
@@ -482,17 +956,36 @@ impl ChatWallet {
             .await
             .context("Error while creating transaction type builder")?;
 
+        // built after the pending transactions, from the builder state of the last one:
+        // the next nonce, with what they spend already deducted
+        let last_pending_state = self.last_pending_state().await;
+
         let (tx, state) = {
             let storage = self.wallet.get_storage().write().await;
-            self.wallet
-                .create_transaction_with_storage(
-                    &storage,
-                    transaction_type_builder.clone(),
-                    FeeBuilder::default(),
-                    BaseFeeMode::None,
-                    None,
-                )
-                .await?
+            match last_pending_state {
+                Some(mut state) => {
+                    let tx_version = storage.get_tx_version().await?;
+                    let tx = self.wallet.create_transaction_with(
+                        &mut state,
+                        tx_version,
+                        transaction_type_builder.clone(),
+                        FeeBuilder::default(),
+                        BaseFeeMode::None,
+                    )?;
+                    (tx, state)
+                }
+                None => {
+                    self.wallet
+                        .create_transaction_with_storage(
+                            &storage,
+                            transaction_type_builder.clone(),
+                            FeeBuilder::default(),
+                            BaseFeeMode::None,
+                            None,
+                        )
+                        .await?
+                }
+            }
         };
 
         let tx_hash = tx.hash();
@@ -528,6 +1021,109 @@ impl ChatWallet {
         }
     }
 
+    /// Finds out what happened to a transaction broadcast by this wallet
+    pub async fn check_sent_transaction(&self, tx_hash: &str) -> Result<SentTxState> {
+        let hash = Hash::from_hex(tx_hash).context("Invalid tx hash")?;
+
+        // confirmed transactions are in wallet storage
+        {
+            let storage = self.wallet.get_storage().read().await;
+            if storage.has_transaction(&hash)? {
+                let entry = storage.get_transaction(&hash)?;
+                return Ok(SentTxState::Confirmed {
+                    topoheight: entry.get_topoheight(),
+                    timestamp: entry.get_timestamp(),
+                });
+            }
+        }
+
+        // otherwise ask the daemon if it still knows about it
+        let network_handler = self.wallet.get_network_handler().lock().await;
+        let Some(handler) = network_handler.as_ref() else {
+            bail!("Wallet is offline, tx {tx_hash} cannot be checked");
+        };
+
+        let api = handler.get_api();
+        match api.get_transaction(&hash).await {
+            Ok(_) => Ok(SentTxState::Unconfirmed),
+            // only a daemon that answers and does not know the tx tells it was dropped,
+            // a timeout or a connection error tells nothing
+            Err(e) => match api.get_info().await {
+                Ok(_) => {
+                    info!("Tx {tx_hash} unknown to the daemon: {e}");
+                    Ok(SentTxState::Dropped)
+                }
+                Err(_) => Err(anyhow!("Cannot check tx {tx_hash}: {e}")),
+            },
+        }
+    }
+
+    /// Fee of a built transaction that can still be broadcast, none once its nonce was used
+    pub async fn valid_pending_transaction(&self, tx_hash: &str) -> Option<u64> {
+        let hash = Hash::from_hex(tx_hash).ok()?;
+        let nonce = self.wallet.get_nonce().await;
+
+        let pending_transactions = self.pending_transactions.read().unwrap();
+        let (tx, _) = pending_transactions.get(&hash)?;
+        let chain_end = chain_end(&pending_nonces(&pending_transactions), nonce);
+
+        (nonce..chain_end)
+            .contains(&tx.get_nonce())
+            .then_some(tx.get_fee())
+    }
+
+    /// Hash of the pending transaction to broadcast next, if one was built
+    pub async fn next_pending_transaction(&self) -> Option<String> {
+        let nonce = self.wallet.get_nonce().await;
+
+        self.pending_transactions
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, (tx, _))| tx.get_nonce() == nonce)
+            .map(|(hash, _)| hash.to_hex())
+    }
+
+    // Builder state of the last pending transaction following the account nonce
+    async fn last_pending_state(&self) -> Option<TransactionBuilderState> {
+        let nonce = self.wallet.get_nonce().await;
+
+        let pending_transactions = self.pending_transactions.read().unwrap();
+        let last_nonce = chain_end(&pending_nonces(&pending_transactions), nonce).checked_sub(1)?;
+        if last_nonce < nonce {
+            return None;
+        }
+
+        pending_transactions
+            .values()
+            .find(|(tx, _)| tx.get_nonce() == last_nonce)
+            .map(|(_, state)| state.clone())
+    }
+
+    /// Drops a transaction that will not be broadcast, with the ones built after it on its state.
+    /// Returns the hashes of the dropped transactions.
+    pub async fn discard_transaction(&self, tx_hash: &str) -> Result<Vec<String>> {
+        let hash = Hash::from_hex(tx_hash)?;
+
+        let mut pending_transactions = self.pending_transactions.write().unwrap();
+        let nonce = pending_transactions
+            .get(&hash)
+            .map(|(tx, _)| tx.get_nonce())
+            .context("Cannot remove the pending transaction")?;
+
+        let discarded: Vec<Hash> = pending_transactions
+            .iter()
+            .filter(|(_, (tx, _))| tx.get_nonce() >= nonce)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in &discarded {
+            pending_transactions.remove(hash);
+            info!("Tx: {hash} removed from pending transactions!");
+        }
+
+        Ok(discarded.iter().map(|hash| hash.to_hex()).collect())
+    }
+
     /// Clears a transaction
     pub async fn clear_transaction(
         &self,
@@ -552,6 +1148,26 @@ impl ChatWallet {
         info!("start to broadcast tx: {}", tx_hash);
 
         if self.wallet.is_online().await {
+            let nonce = self.wallet.get_nonce().await;
+
+            // the builder state of a transaction built before another one was broadcast
+            // spends the same nonce and balance, it has to be built again
+            if self.valid_pending_transaction(&tx_hash).await.is_none() {
+                bail!("Transaction {tx_hash} is stale, its nonce was used by another transaction");
+            }
+
+            // the pending transactions go out in the order they were built
+            let hash = Hash::from_hex(&tx_hash)?;
+            let is_next = self
+                .pending_transactions
+                .read()
+                .unwrap()
+                .get(&hash)
+                .is_some_and(|(tx, _)| tx.get_nonce() == nonce);
+            if !is_next {
+                bail!("Transaction {tx_hash} waits for the transactions built before it");
+            }
+
             let (tx, mut state) = self.clear_transaction(tx_hash.clone()).await?;
             let mut storage = self.wallet.get_storage().write().await;
 
@@ -590,19 +1206,13 @@ impl ChatWallet {
         for transfer in transfers {
             let asset = Hash::from_hex(&transfer.asset_hash).context("Invalid asset")?;
 
-            let amount = self
-                .convert_float_amount(transfer.float_amount, &asset)
-                .await?;
-
             let address = Address::from_string(&transfer.str_address).context("Invalid address")?;
 
-            let extra_data = transfer
-                .extra_data
-                .map(|data| DataElement::Value(DataValue::String(data)));
+            let extra_data = transfer.extra_data;
 
             let transfer_builder = TransferBuilder {
                 destination: address,
-                amount,
+                amount: transfer.amount,
                 asset,
                 extra_data: extra_data.clone(),
                 encrypt_extra_data: extra_data.is_some(),
@@ -618,7 +1228,186 @@ impl ChatWallet {
     async fn convert_float_amount(&self, float_amount: f64, asset: &Hash) -> Result<u64> {
         let storage = self.wallet.get_storage().read().await;
         let decimals = storage.get_asset(asset).await?.get_decimals();
-        let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
+        // rounded, 0.29 is stored as 0.28999999999999998
+        let amount = (float_amount * 10u64.pow(decimals as u32) as f64).round() as u64;
         Ok(amount)
     }
 }
+
+// Nonces of the pending transactions
+fn pending_nonces(
+    pending_transactions: &HashMap<Hash, (Transaction, TransactionBuilderState)>,
+) -> Vec<u64> {
+    pending_transactions
+        .values()
+        .map(|(tx, _)| tx.get_nonce())
+        .collect()
+}
+
+// First nonce after the pending transactions that follow each other from the account nonce
+fn chain_end(pending_nonces: &[u64], nonce: u64) -> u64 {
+    let mut next_nonce = nonce;
+    while pending_nonces.contains(&next_nonce) {
+        next_nonce += 1;
+    }
+
+    next_nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(hash: &Hash) -> TransactionEntry {
+        TransactionEntry {
+            hash: hash.clone(),
+            topoheight: 42,
+            timestamp: 1_700_000_000,
+            entry: EntryType::Coinbase { reward: 1 },
+        }
+    }
+
+    #[test]
+    fn connection_events_follow_the_node() {
+        let mut state = WalletState::default();
+
+        let events = state.handle_event(Event::Online);
+        assert!(matches!(events[..], [AppEvent::Online]));
+        assert!(state.is_online);
+
+        let events = state.handle_event(Event::Offline);
+        assert!(matches!(events[..], [AppEvent::Offline]));
+        assert!(!state.is_online);
+    }
+
+    #[test]
+    fn rescan_waits_for_the_history_again() {
+        let mut state = WalletState::default();
+
+        let events = state.handle_event(Event::HistorySynced { topoheight: 100 });
+        assert!(matches!(events[..], [AppEvent::HistorySynced]));
+        assert!(state.history_synced);
+
+        let events = state.handle_event(Event::Rescan {
+            start_topoheight: 10,
+        });
+        assert!(matches!(events[..], [AppEvent::Rescan(10)]));
+        assert!(!state.history_synced);
+    }
+
+    #[test]
+    fn sync_errors_leave_the_state_alone() {
+        let mut state = WalletState {
+            is_online: true,
+            history_synced: true,
+            ..Default::default()
+        };
+
+        let events = state.handle_event(Event::SyncError {
+            message: "daemon unreachable".to_string(),
+        });
+        assert!(
+            matches!(&events[..], [AppEvent::SyncError(message)] if message == "daemon unreachable")
+        );
+        assert!(state.is_online);
+        assert!(state.history_synced);
+    }
+
+    #[test]
+    fn new_assets_are_published() {
+        let mut state = WalletState::default();
+
+        // the asset itself is not read
+        let asset = serde_json::from_value(serde_json::json!({
+            "asset": XELIS_ASSET,
+            "topoheight": 0,
+            "decimals": COIN_DECIMALS,
+            "name": "XELIS",
+            "ticker": "XEL",
+            "max_supply": { "fixed": 1_840_000_000_000_000u64 },
+            "owner": "none",
+        }))
+        .unwrap();
+
+        let events = state.handle_event(Event::NewAsset(asset));
+        assert!(matches!(events[..], [AppEvent::NewAsset]));
+    }
+
+    #[test]
+    fn new_topoheight_is_kept() {
+        let mut state = WalletState::default();
+
+        let events = state.handle_event(Event::NewTopoHeight { topoheight: 7 });
+        assert!(matches!(events[..], [AppEvent::NewTopoHeight(7)]));
+        assert_eq!(state.topoheight, 7);
+    }
+
+    #[test]
+    fn sent_transactions_are_confirmed_once() {
+        let hash = Hash::new([1; 32]);
+        let mut state = WalletState {
+            sent_tx_hashes: vec![hash.to_string()],
+            ..Default::default()
+        };
+
+        let events = state.handle_event(Event::NewTransaction(transaction(&hash)));
+        match &events[..] {
+            [AppEvent::MessageConfirmed(message)] => {
+                assert_eq!(message.hash, hash.to_string());
+                assert_eq!(message.status, MessageStatus::Confirmed);
+                assert_eq!(message.topoheight, 42);
+            }
+            events => panic!("unexpected events: {events:?}"),
+        }
+        assert!(state.sent_tx_hashes.is_empty());
+
+        // replayed by a rescan
+        let events = state.handle_event(Event::NewTransaction(transaction(&hash)));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn other_transactions_only_change_the_balance() {
+        let mut state = WalletState {
+            sent_tx_hashes: vec![Hash::new([1; 32]).to_string()],
+            ..Default::default()
+        };
+
+        let events = state.handle_event(Event::NewTransaction(transaction(&Hash::new([2; 32]))));
+        assert!(events.is_empty());
+        assert_eq!(state.sent_tx_hashes.len(), 1);
+    }
+
+    #[test]
+    fn orphaned_and_removed_transactions_orphan_their_messages() {
+        let hash = Hash::new([3; 32]);
+
+        for event in [
+            Event::TransactionOrphaned(transaction(&hash)),
+            Event::TransactionRemoved(transaction(&hash)),
+        ] {
+            let mut state = WalletState::default();
+
+            let events = state.handle_event(event);
+            match &events[..] {
+                [AppEvent::MessageOrphaned(message)] => {
+                    assert_eq!(message.hash, hash.to_string());
+                    assert_eq!(message.status, MessageStatus::Orphaned);
+                }
+                events => panic!("unexpected events: {events:?}"),
+            }
+            // only sent transactions wait for a confirmation
+            assert!(state.sent_tx_hashes.is_empty());
+        }
+    }
+
+    #[test]
+    fn pending_transactions_chain_from_the_account_nonce() {
+        assert_eq!(chain_end(&[], 7), 7);
+        assert_eq!(chain_end(&[8, 7, 9], 7), 10);
+        // a transaction past a gap is not part of the chain
+        assert_eq!(chain_end(&[7, 9], 7), 8);
+        // nor one whose nonce was used already
+        assert_eq!(chain_end(&[5, 6], 7), 7);
+    }
+}
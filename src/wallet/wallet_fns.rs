@@ -1,20 +1,375 @@
 use crate::{
-    WALLET,
-    database::db_fns::{DbUserLogin, db_store_init_message, db_update_status_fee},
-    views::DbMessage,
+    ACCOUNT, DB, SETTINGS, WALLET,
+    database::db_fns::{
+        db_enqueue_outbox, db_fail_unbroadcast_messages, db_import_messages, db_outbox_failed,
+        db_outbox_open_window, db_outbox_retry_later, db_outbox_sent, db_read_due_outbox,
+        db_read_message, db_read_nodes, db_read_pending_messages, db_read_user, db_remove_outbox,
+        db_rescan_messages, db_store_init_message, db_update_status_fee,
+        db_update_status_topoheight, verify_password,
+    },
+    views::{DbMessage, DbOutboxItem, MessageDirection, MessageStatus},
 };
 use dioxus::{
     hooks::UseFuture,
     logger::tracing::info,
     signals::{Readable, Signal, Writable},
 };
-use sqlx::{Error, SqlitePool, query_as};
+use sqlx::SqlitePool;
+use uuid::Uuid;
 use xelis_common::{config::XELIS_ASSET, utils::format_xelis};
 
-use super::utils::Transfer;
+use super::{
+    events::{AppEvent, publish_app_event, stop_event_dispatcher},
+    message::{Envelope, MAX_MESSAGE_PARTS, encode_message, parts_needed},
+    utils::{
+        ChatWallet, NodeHealth, Payment, SentTxState, Transfer, WalletNetwork, probe_node,
+        rank_nodes,
+    },
+};
+
+// Outbox retry delays and the attempts before a message fails for good
+const OUTBOX_BASE_DELAY_SECS: i64 = 10;
+const OUTBOX_MAX_DELAY_SECS: i64 = 600;
+const OUTBOX_MAX_ATTEMPTS: i64 = 8;
+
+// Developer fee transfer added to every message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DevFee {
+    pub address: &'static str,
+    // XEL per message
+    pub amount: f64,
+}
+
+/// Default dev fee of a network, none where there is no dev fee address
+pub fn default_dev_fee(network: WalletNetwork) -> Option<DevFee> {
+    match network {
+        WalletNetwork::Mainnet => None, // no mainnet dev fee address yet
+        WalletNetwork::Testnet => Some(DevFee {
+            address: "xet:gqef8a3qusf476lcqv0f4us947swgf38yrrs3x9npltjzh7mrcrqqgvgex3",
+            amount: 0.01,
+        }),
+        WalletNetwork::Devnet => None,
+    }
+}
+
+/// Dev fee with the amount picked in the settings, none when it is disabled
+pub fn dev_fee(network: WalletNetwork) -> Option<DevFee> {
+    let settings = SETTINGS.read();
+    if !settings.dev_fee_enabled {
+        return None;
+    }
+
+    let mut dev_fee = default_dev_fee(network)?;
+    if let Some(amount) = settings.dev_fee_amount {
+        dev_fee.amount = amount;
+    }
+
+    (dev_fee.amount > 0.0).then_some(dev_fee)
+}
+
+/// Closes the open wallet, if any
+pub async fn wallet_close() {
+    stop_event_dispatcher();
+
+    let wallet = WALLET.write().take();
+
+    if let Some(wallet) = wallet {
+        let wallet = wallet.into_inner();
+
+        if wallet.is_online().await {
+            if let Err(e) = wallet.set_offline().await {
+                info!("set_offline error: {e}");
+            }
+        }
+
+        if let Err(e) = wallet.close_wallet().await {
+            info!("close_wallet error: {e}");
+        }
+
+        info!("Wallet closed");
+    }
+}
+
+/// Closes the wallet and the database pool so no secret stays in memory while locked
+pub async fn wallet_lock() {
+    wallet_close().await;
+
+    let db = DB.write().take();
+
+    if let Some(db) = db {
+        db.close().await;
+        info!("Database closed");
+    }
+}
+
+/// Sets the wallet online on the healthiest node of its network registry.
+/// The nodes are probed without holding the wallet, it is only locked to switch the node.
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_connect_best_node() -> anyhow::Result<NodeHealth> {
+    let network = match &*WALLET.read() {
+        Some(wallet) => wallet.read().await.network,
+        None => return Err(anyhow::anyhow!("Wallet not initialized")),
+    };
+
+    // try the node picked in the settings first
+    let preferred_node = SETTINGS.read().node_endpoint.clone();
+    if let Some(preferred_node) = preferred_node {
+        let healths = rank_nodes(vec![preferred_node], network).await;
+        if !healths.is_empty() {
+            match wallet_switch_node(healths).await {
+                Ok(node) => return Ok(node),
+                Err(e) => info!("Preferred node error: {e}"),
+            }
+        }
+    }
+
+    // user added nodes and built-in ones
+    let endpoints = match &*DB.read() {
+        Some(db) => db_read_nodes(db, network)
+            .await
+            .into_iter()
+            .map(|node| node.endpoint)
+            .collect(),
+        None => network
+            .default_nodes()
+            .iter()
+            .map(|endpoint| endpoint.to_string())
+            .collect(),
+    };
+
+    let healths = rank_nodes(endpoints, network).await;
+    wallet_switch_node(healths).await
+}
+
+// Sets the wallet online on the first of the probed nodes that accepts it
+#[allow(clippy::await_holding_invalid_type)]
+async fn wallet_switch_node(healths: Vec<NodeHealth>) -> anyhow::Result<NodeHealth> {
+    let node = match &*WALLET.read() {
+        Some(wallet) => wallet.write().await.connect_node(healths).await?,
+        None => return Err(anyhow::anyhow!("Wallet not initialized")),
+    };
+
+    publish_app_event(AppEvent::NodeChanged(node.clone()));
+    Ok(node)
+}
+
+/// Probes the active node again without holding the wallet, returns false if it is down
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_check_active_node() -> bool {
+    let (active_node, network) = match &*WALLET.read() {
+        Some(wallet) => {
+            let wallet = wallet.read().await;
+            (wallet.active_node.clone(), wallet.network)
+        }
+        None => return false,
+    };
+    let Some(active_node) = active_node else {
+        return false;
+    };
+
+    let health = probe_node(active_node.endpoint, network).await;
+
+    let is_online = match &*WALLET.read() {
+        Some(wallet) => {
+            let mut wallet = wallet.write().await;
+            // the node may have been switched during the probe
+            let is_active = wallet
+                .active_node
+                .as_ref()
+                .is_some_and(|node| node.endpoint == health.endpoint);
+            if !is_active {
+                return true;
+            }
+
+            wallet.active_node = Some(health.clone());
+            wallet.is_online().await
+        }
+        None => return false,
+    };
+
+    let is_healthy = health.is_healthy && is_online;
+    publish_app_event(AppEvent::NodeChanged(health));
+
+    is_healthy
+}
+
+/// Settles the outgoing messages left unconfirmed when the app was last closed
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_reconcile_pending() {
+    let Some(account_id) = *ACCOUNT.read() else {
+        return;
+    };
+
+    let opened_at = match &*WALLET.read() {
+        Some(wallet) => wallet.read().await.opened_at,
+        None => return,
+    };
+
+    let pending_messages = match &*DB.read() {
+        Some(db) => {
+            // messages built by the last session were never broadcast,
+            // the ones built since may be confirmed by the user right now
+            match db_fail_unbroadcast_messages(db, account_id, opened_at).await {
+                Ok(count) => info!("{count} unbroadcast messages marked as failed"),
+                Err(e) => info!("{e}"),
+            }
+
+            match db_read_pending_messages(db, account_id).await {
+                Ok(pending_messages) => pending_messages,
+                Err(e) => {
+                    info!("{e}");
+                    return;
+                }
+            }
+        }
+        None => {
+            info!("DB read error");
+            return;
+        }
+    };
+
+    if let Some(wallet) = &*WALLET.read() {
+        for mut message in pending_messages {
+            let state = wallet
+                .read()
+                .await
+                .check_sent_transaction(&message.hash)
+                .await;
+
+            match state {
+                Ok(SentTxState::Dropped) => {
+                    // only this message, a never broadcast one has no hash to match on
+                    message.set_status(MessageStatus::Failed);
+                    db_update_status_fee(message).await;
+                }
+                Ok(state) => settle_sent_message(message, state).await,
+                Err(e) => info!("Reconcile error for {}: {e}", message.hash),
+            }
+        }
+
+        wallet.write().await.pending_reconciled = true;
+    }
+}
+
+// Stores a sent message whose transaction the daemon or the wallet storage knows about
+#[allow(clippy::await_holding_invalid_type)]
+async fn settle_sent_message(mut message: DbMessage, state: SentTxState) {
+    match state {
+        SentTxState::Confirmed {
+            topoheight,
+            timestamp,
+        } => {
+            message.set_status(MessageStatus::Confirmed);
+            message.topoheight = topoheight as i64;
+            message.timestamp = timestamp as i64;
+            db_update_status_topoheight(message).await;
+        }
+        SentTxState::Unconfirmed => {
+            // the live confirmation updates it later
+            if let Some(wallet) = &*WALLET.read() {
+                let mut wallet = wallet.write().await;
+                if !wallet.state.sent_tx_hashes.contains(&message.hash) {
+                    wallet.state.sent_tx_hashes.push(message.hash.clone());
+                }
+            }
+
+            message.set_status(MessageStatus::InMempool);
+            db_update_status_fee(message).await;
+        }
+        SentTxState::Dropped => {}
+    }
+}
+
+// Status of a message whose transaction was just broadcast, InMempool once the daemon holds it
+async fn broadcast_status(wallet: &ChatWallet, tx_hash: &str) -> MessageStatus {
+    match wallet.check_sent_transaction(tx_hash).await {
+        Ok(SentTxState::Unconfirmed) => MessageStatus::InMempool,
+        _ => MessageStatus::Broadcast,
+    }
+}
+
+// What became of the last transaction tried with a message, none when it has not been tried
+#[allow(clippy::await_holding_invalid_type)]
+async fn sent_message_state(db_message: &DbMessage) -> anyhow::Result<Option<SentTxState>> {
+    if db_message.hash.is_empty() {
+        return Ok(None);
+    }
+
+    match &*WALLET.read() {
+        Some(wallet) => Ok(Some(
+            wallet
+                .read()
+                .await
+                .check_sent_transaction(&db_message.hash)
+                .await?,
+        )),
+        None => Err(anyhow::anyhow!("Wallet not initialized")),
+    }
+}
+
+/// Puts back the messages above the rescan start, the rescan confirms them again
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_rescan_messages(start_topoheight: u64) {
+    let Some(account_id) = *ACCOUNT.read() else {
+        return;
+    };
+
+    let unconfirmed_hashes = match &*DB.read() {
+        Some(db) => match db_rescan_messages(db, account_id, start_topoheight as i64).await {
+            Ok(unconfirmed_hashes) => unconfirmed_hashes,
+            Err(e) => {
+                info!("Rescan error: {e}");
+                return;
+            }
+        },
+        None => return,
+    };
+
+    // outgoing messages wait for their confirmation again
+    if let Some(wallet) = &*WALLET.read() {
+        let mut wallet = wallet.write().await;
+        for hash in unconfirmed_hashes {
+            if !wallet.state.sent_tx_hashes.contains(&hash) {
+                wallet.state.sent_tx_hashes.push(hash);
+            }
+        }
+    }
+}
+
+/// Rebuilds the chat history of the open account from its wallet transactions
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_import_history(import_progress: &mut Signal<String>) {
+    let Some(account_id) = *ACCOUNT.read() else {
+        return;
+    };
 
-static DEV_FEE_ADDRESS: &str = "xet:gqef8a3qusf476lcqv0f4us947swgf38yrrs3x9npltjzh7mrcrqqgvgex3"; // testnet
-pub static DEV_FEE_AMOUNT: f64 = 0.01;
+    import_progress.set("Scanning wallet history...".to_string());
+
+    let messages = match &*WALLET.read() {
+        Some(wallet) => wallet.read().await.get_history_messages().await,
+        None => {
+            info!("Wallet not initialized");
+            return;
+        }
+    };
+
+    match messages {
+        Ok(messages) => match &*DB.read() {
+            Some(db) => match db_import_messages(db, account_id, messages, import_progress).await {
+                Ok(count) => import_progress.set(format!("{count} messages imported")),
+                Err(e) => {
+                    info!("History import error: {e}");
+                    import_progress.set(format!("History import error: {e}"));
+                }
+            },
+            None => info!("DB read error"),
+        },
+        Err(e) => {
+            info!("History scan error: {e}");
+            import_progress.set(format!("History scan error: {e}"));
+        }
+    }
+}
 
 #[allow(
     clippy::redundant_closure,
@@ -26,18 +381,27 @@ pub async fn wallet_get_seed(
     entered_password: String,
     seed_phrase_info: &mut Signal<String>,
 ) {
-    let db_user: Result<DbUserLogin, Error> = query_as("SELECT username, password FROM user")
-        .fetch_one(&*db)
-        .await;
+    let account_id = match *ACCOUNT.read() {
+        Some(account_id) => account_id,
+        None => {
+            seed_phrase_info.set("No account selected".to_string());
+            return;
+        }
+    };
 
-    match db_user {
+    match db_read_user(db, account_id).await {
         Ok(db_user) => {
-            if entered_password == db_user.password {
+            let is_valid = match &db_user.password_hash {
+                Some(password_hash) => verify_password(&entered_password, password_hash).await,
+                None => false,
+            };
+
+            if is_valid {
                 if let Some(wallet) = &*WALLET.read() {
                     match wallet
                         .read()
                         .await
-                        .get_mnemonic(crate::wallet::utils::MnemonicLanguage::English)
+                        .get_mnemonic(SETTINGS.read().mnemonic_language)
                         .await
                     {
                         Ok(seed) => {
@@ -65,98 +429,670 @@ pub async fn wallet_get_seed(
     }
 }
 
+/// Converts a float amount of an asset to atomic units
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_atomic_amount(float_amount: f64, asset_hash: &str) -> anyhow::Result<u64> {
+    if !float_amount.is_finite() || float_amount <= 0.0 {
+        return Err(anyhow::anyhow!("Invalid amount"));
+    }
+
+    match &*WALLET.read() {
+        Some(wallet) => {
+            wallet
+                .read()
+                .await
+                .atomic_amount(float_amount, asset_hash)
+                .await
+        }
+        None => Err(anyhow::anyhow!("Wallet not initialized")),
+    }
+}
+
+/// New id for an outgoing envelope
+pub fn new_message_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+// A built message transaction waiting for the user to confirm it
+#[derive(Clone, Debug)]
+pub struct PendingSend {
+    pub db_message: DbMessage,
+    pub tx_hash: String,
+    // XELIS atomic units
+    pub network_fee: u64,
+    pub dev_fee: u64,
+    pub dev_fee_address: Option<&'static str>,
+}
+
+impl PendingSend {
+    /// Cost in XELIS atomic units, a payment in another asset is not included
+    pub fn total_xelis(&self) -> u64 {
+        let payment = if self.db_message.asset == XELIS_ASSET.to_hex() {
+            self.db_message.amount as u64
+        } else {
+            0
+        };
+
+        self.network_fee + self.dev_fee + payment
+    }
+}
+
+// Transfers of a message, the payment goes with the first part
+fn message_transfers(db_message: &DbMessage) -> anyhow::Result<Vec<Transfer>> {
+    // long messages are split in parts, one transfer each
+    let message_parts = encode_message(&db_message.to_envelope())?;
+
+    // the stored atomic amount is sent as is
+    let payment = (db_message.amount > 0).then(|| Payment {
+        amount: db_message.amount as u64,
+        asset_hash: db_message.asset.clone(),
+    });
+
+    Ok(message_parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, message_part)| {
+            let (amount, asset_hash) = match &payment {
+                Some(payment) if i == 0 => (payment.amount, payment.asset_hash.clone()),
+                _ => (0, XELIS_ASSET.to_string()),
+            };
+
+            Transfer {
+                amount,
+                str_address: db_message.address.clone(),
+                asset_hash,
+                extra_data: Some(message_part),
+            }
+        })
+        .collect())
+}
+
+// Transfers of the messages sent in one transaction with a single dev fee,
+// every message records its share of the dev fee
+async fn messages_transfers(
+    wallet: &ChatWallet,
+    db_messages: &mut [&mut DbMessage],
+) -> anyhow::Result<(Vec<Transfer>, Option<DevFee>, u64)> {
+    let mut transfers = Vec::new();
+
+    // the dev fee of the wallet network goes first, if there is one and it is enabled
+    let network_dev_fee = dev_fee(wallet.network);
+    let mut dev_fee_amount = 0;
+    if let Some(network_dev_fee) = network_dev_fee {
+        dev_fee_amount = wallet
+            .atomic_amount(network_dev_fee.amount, &XELIS_ASSET.to_hex())
+            .await?;
+
+        transfers.push(Transfer {
+            amount: dev_fee_amount,
+            str_address: network_dev_fee.address.to_string(),
+            asset_hash: XELIS_ASSET.to_string(),
+            extra_data: None,
+        });
+    }
+
+    // every message records the transfer carrying its first part
+    for db_message in db_messages.iter_mut() {
+        db_message.transfer_index = transfers.len() as i64;
+        transfers.extend(message_transfers(db_message)?);
+    }
+
+    // recorded with the messages, apart from the network fee
+    let dev_fee_share = format_xelis(dev_fee_amount)
+        .parse::<f64>()
+        .unwrap_or_default()
+        / db_messages.len().max(1) as f64;
+    for db_message in db_messages.iter_mut() {
+        db_message.dev_fee = dev_fee_share;
+    }
+
+    Ok((transfers, network_dev_fee, dev_fee_amount))
+}
+
+// New outgoing draft, the payment amount is stored in atomic units
+fn new_outgoing_message(
+    contact_address: String,
+    topoheight: i64,
+    mut envelope: Envelope,
+    payment: Option<Payment>,
+) -> DbMessage {
+    // plain text goes out as a bare string without its id, replies refer to it by tx hash
+    if envelope.is_plain_text() {
+        envelope.id.clear();
+    }
+
+    let (asset, amount) = match payment {
+        Some(payment) => (payment.asset_hash, payment.amount as i64),
+        None => (XELIS_ASSET.to_string(), 0),
+    };
+
+    let mut db_message = DbMessage {
+        local_id: DbMessage::new_local_id(),
+        status: MessageStatus::Draft,
+        direction: MessageDirection::Outgoing,
+        address: contact_address,
+        hash: Default::default(),
+        fee: Default::default(),
+        // creation time until the block timestamp replaces it
+        timestamp: chrono::Utc::now().timestamp(),
+        topoheight,
+        asset,
+        amount,
+        ..Default::default()
+    };
+    db_message.set_envelope(envelope);
+
+    db_message
+}
+
+/// Builds the transaction of a message without broadcasting it, so its cost can be shown first
+pub async fn wallet_build_message(
+    contact_address: String,
+    topoheight: i64,
+    envelope: Envelope,
+    payment: Option<Payment>,
+    info: &mut Signal<String>,
+) -> Option<PendingSend> {
+    let db_message = new_outgoing_message(contact_address, topoheight, envelope, payment);
+
+    build_pending_send(db_message, info).await
+}
+
+/// Builds the message of a preview again, after an earlier transaction it was built on
+/// was dropped or its nonce was used. The user confirms its cost again.
+pub async fn wallet_rebuild_message(
+    pending_send: PendingSend,
+    info: &mut Signal<String>,
+) -> Option<PendingSend> {
+    build_pending_send(pending_send.db_message, info).await
+}
+
+// Builds the transaction of an outgoing message, after the pending ones
 #[allow(
     clippy::redundant_closure,
     clippy::await_holding_invalid_type,
     clippy::borrow_deref_ref
 )]
-pub async fn wallet_send_message(
+async fn build_pending_send(
+    mut db_message: DbMessage,
+    info: &mut Signal<String>,
+) -> Option<PendingSend> {
+    // get wallet handle
+    match &*WALLET.read() {
+        Some(wallet_rw) => {
+            let mut wallet = wallet_rw.write().await;
+
+            let (transfers, network_dev_fee, dev_fee_amount) =
+                match messages_transfers(&wallet, &mut [&mut db_message]).await {
+                    Ok(messages_transfers) => messages_transfers,
+                    Err(e) => {
+                        info!("{e}");
+                        info.set(e.to_string());
+                        return None;
+                    }
+                };
+
+            match wallet.create_transfers_transaction(transfers).await {
+                Ok(transaction_summary) => {
+                    db_message.set_status(MessageStatus::Built);
+
+                    Some(PendingSend {
+                        db_message,
+                        tx_hash: transaction_summary.hash,
+                        network_fee: transaction_summary.fee,
+                        dev_fee: dev_fee_amount,
+                        dev_fee_address: network_dev_fee
+                            .map(|network_dev_fee| network_dev_fee.address),
+                    })
+                }
+                Err(e) => {
+                    info!("{}", e);
+                    info.set(format!("error_2: {}", e).to_string());
+                    None
+                }
+            }
+        }
+        None => {
+            info!("Error reading wallet");
+            None
+        }
+    }
+}
+
+/// Stores a message in the outbox, it is sent with the others queued within the batch window
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_queue_message(
     contact_address: String,
     topoheight: i64,
-    message: String,
+    envelope: Envelope,
+    payment: Option<Payment>,
     db_message_handle: &mut UseFuture,
     info: &mut Signal<String>,
 ) {
-    let mut db_message = DbMessage {
-        status: "Pending".to_string(),
-        direction: "Outgoing".to_string(),
-        address: contact_address.clone(),
-        hash: Default::default(),
-        fee: Default::default(),
-        timestamp: Default::default(),
-        topoheight,
-        asset: XELIS_ASSET.to_string(),
-        amount: Default::default(),
-        message: Some(message),
+    // a message too long to be sent is refused now, not by the worker
+    if let Err(e) = encode_message(&envelope) {
+        info.set(e.to_string());
+        return;
+    }
+
+    let mut db_message = new_outgoing_message(contact_address, topoheight, envelope, payment);
+
+    let Some(account_id) = *ACCOUNT.read() else {
+        return;
     };
 
-    // store it in db
+    db_message.set_status(MessageStatus::Queued);
+
     db_store_init_message(db_message.clone()).await;
 
+    if let Some(db) = &*DB.read() {
+        // the messages queued within a window are all sent at its end, in one transaction
+        let now = chrono::Utc::now().timestamp();
+        let next_attempt_at = match db_outbox_open_window(db, account_id, now).await {
+            Ok(Some(deadline)) => deadline,
+            Ok(None) => now + SETTINGS.read().batch_window_secs as i64,
+            Err(e) => {
+                info!("Outbox read error: {e}");
+                now + SETTINGS.read().batch_window_secs as i64
+            }
+        };
+
+        if let Err(e) =
+            db_enqueue_outbox(db, account_id, &db_message, next_attempt_at, None, None).await
+        {
+            info!("Outbox error: {e}");
+            info.set(format!("Outbox error: {e}"));
+        }
+    }
+
     // reload database
     db_message_handle.restart();
+}
 
-    let dev_transfer = Transfer {
-        float_amount: DEV_FEE_AMOUNT,
-        str_address: DEV_FEE_ADDRESS.to_string(),
-        asset_hash: XELIS_ASSET.to_string(),
-        extra_data: None,
+/// Stores a confirmed message and broadcasts its transaction.
+/// Messages that cannot be broadcast now go to the outbox.
+/// A transaction gone stale is not sent at a cost the user did not see,
+/// the message is built again and returned to be confirmed.
+#[allow(
+    clippy::redundant_closure,
+    clippy::await_holding_invalid_type,
+    clippy::borrow_deref_ref
+)]
+pub async fn wallet_confirm_message(
+    pending_send: PendingSend,
+    db_message_handle: &mut UseFuture,
+    info: &mut Signal<String>,
+) -> Option<PendingSend> {
+    let is_valid = match &*WALLET.read() {
+        Some(wallet) => wallet
+            .read()
+            .await
+            .valid_pending_transaction(&pending_send.tx_hash)
+            .await
+            .is_some(),
+        None => false,
     };
+    if !is_valid {
+        let rebuilt = wallet_rebuild_message(pending_send, info).await;
+        if rebuilt.is_some() {
+            info.set("The message was built again, confirm its new cost".to_string());
+        }
+        return rebuilt;
+    }
 
-    let msg_transfer = Transfer {
-        float_amount: 0.0,
-        str_address: contact_address.clone(),
-        asset_hash: XELIS_ASSET.to_string(),
-        extra_data: db_message.message.clone(),
-    };
+    let mut db_message = pending_send.db_message;
+
+    // store it in db
+    db_store_init_message(db_message.clone()).await;
 
-    // create the vector of transfers
-    let transfers = vec![dev_transfer, msg_transfer];
+    // reload database
+    db_message_handle.restart();
 
     // get wallet handle
-    match &*WALLET.read() {
+    let broadcast = match &*WALLET.read() {
         Some(wallet_rw) => {
             let mut wallet = wallet_rw.write().await;
 
-            match wallet.create_transfers_transaction(transfers).await {
-                Ok(transaction_summary) => {
-                    match wallet
-                        .broadcast_transaction(transaction_summary.hash.clone())
+            match wallet
+                .broadcast_transaction(pending_send.tx_hash.clone())
+                .await
+            {
+                Ok(_) => {
+                    info!("Message sent successfully");
+
+                    wallet
+                        .state
+                        .sent_tx_hashes
+                        .push(pending_send.tx_hash.clone());
+
+                    db_message.set_status(MessageStatus::Broadcast);
+                    db_message.set_status(broadcast_status(&wallet, &pending_send.tx_hash).await);
+                    db_message.hash = pending_send.tx_hash.clone();
+
+                    match format_xelis(pending_send.network_fee).parse::<f64>() {
+                        Ok(fee) => db_message.fee = fee,
+                        Err(e) => info!("Fee parsing error: {e}"),
+                    }
+
+                    //update the message in db
+                    db_update_status_fee(db_message.clone()).await;
+
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        None => Err(anyhow::anyhow!("Wallet not initialized")),
+    };
+
+    if let Err(e) = broadcast {
+        // handed to the outbox worker with its built transaction, sent once the wallet can
+        // while its nonce is free, unless this broadcast went through after all
+        db_message.hash = pending_send.tx_hash.clone();
+
+        let queued = match (*ACCOUNT.read(), &*DB.read()) {
+            (Some(account_id), Some(db)) => db_enqueue_outbox(
+                db,
+                account_id,
+                &db_message,
+                chrono::Utc::now().timestamp(),
+                Some(pending_send.tx_hash.clone()),
+                Some(e.to_string()),
+            )
+            .await
+            .map_err(anyhow::Error::from),
+            _ => Err(anyhow::anyhow!("DB not accessible")),
+        };
+
+        match queued {
+            Ok(_) => {
+                info!("Message queued: {e}");
+                info.set(format!("Message queued, it is sent when possible: {e}"));
+            }
+            Err(queue_error) => {
+                // no one would broadcast it, its nonce is freed for the next messages
+                if let Some(wallet) = &*WALLET.read() {
+                    let _ = wallet
+                        .read()
                         .await
-                    {
-                        Ok(_) => {
-                            info!("Message sent successfully");
+                        .discard_transaction(&pending_send.tx_hash)
+                        .await;
+                }
 
-                            wallet.sent_tx_hashes.push(transaction_summary.hash.clone());
+                db_message.set_status(MessageStatus::Failed);
+                db_update_status_fee(db_message).await;
 
-                            db_message.status = "Sent".to_string();
-                            db_message.hash = transaction_summary.hash;
+                info!("Outbox error: {queue_error}");
+                info.set(format!(
+                    "Message not sent: {e}, outbox error: {queue_error}"
+                ));
+            }
+        }
+    }
 
-                            match format_xelis(transaction_summary.fee).parse::<f64>() {
-                                Ok(fee) => db_message.fee = fee,
-                                Err(e) => info!("Fee parsing error: {e}"),
-                            }
+    // reload database
+    db_message_handle.restart();
 
-                            //update the message in db
-                            db_update_status_fee(db_message).await;
+    None
+}
 
-                            // reload database
-                            db_message_handle.restart();
-                        }
-                        Err(e) => {
-                            info!("{}", e);
-                            info.set(format!("error_1: {}", e).to_string());
-                        }
-                    }
+/// Queues a failed message again, the outbox worker sends it on its next run.
+/// A message whose transaction went through after all is not sent twice, false is returned.
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_retry_message(db_message: DbMessage) -> anyhow::Result<bool> {
+    let account_id = (*ACCOUNT.read()).ok_or(anyhow::anyhow!("No account selected"))?;
+
+    // a daemon that cannot tell what became of the last attempt refuses the retry
+    match sent_message_state(&db_message).await? {
+        None | Some(SentTxState::Dropped) => {}
+        Some(state) => {
+            settle_sent_message(db_message, state).await;
+            return Ok(false);
+        }
+    }
+
+    match &*DB.read() {
+        Some(db) => {
+            let now = chrono::Utc::now().timestamp();
+            db_enqueue_outbox(db, account_id, &db_message, now, None, None).await?;
+            Ok(true)
+        }
+        None => Err(anyhow::anyhow!("DB not accessible")),
+    }
+}
+
+// Delay before the next attempt, doubled after every failure
+fn outbox_backoff(attempts: i64) -> i64 {
+    let delay = OUTBOX_BASE_DELAY_SECS.saturating_mul(1 << attempts.clamp(0, 16));
+    delay.min(OUTBOX_MAX_DELAY_SECS)
+}
+
+// Messages of the outbox sent together
+type OutboxBatch = Vec<(DbOutboxItem, DbMessage)>;
+
+// Groups the messages that fit in one transaction, a message with a built transaction is sent alone
+fn outbox_batches(
+    entries: Vec<(DbOutboxItem, DbMessage)>,
+    batch_messages: bool,
+) -> Vec<OutboxBatch> {
+    let mut batches: Vec<OutboxBatch> = Vec::new();
+    let mut batch: OutboxBatch = Vec::new();
+    let mut batch_parts = 0;
+
+    for (item, db_message) in entries {
+        let parts = parts_needed(db_message.message.as_deref().unwrap_or_default());
+
+        if !batch_messages || item.tx_hash.is_some() {
+            batches.push(vec![(item, db_message)]);
+            continue;
+        }
+
+        if !batch.is_empty() && batch_parts + parts > MAX_MESSAGE_PARTS {
+            batches.push(std::mem::take(&mut batch));
+            batch_parts = 0;
+        }
+
+        batch_parts += parts;
+        batch.push((item, db_message));
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+// Broadcasts a batch of outbox messages in one transaction,
+// a single message reuses its built transaction while its nonce is still free
+async fn wallet_send_outbox_batch(
+    wallet: &mut ChatWallet,
+    batch: &mut OutboxBatch,
+) -> anyhow::Result<String> {
+    let cached_tx_hash = match batch.as_slice() {
+        [(item, _)] => item.tx_hash.clone(),
+        _ => None,
+    };
+    let cached = match cached_tx_hash {
+        Some(tx_hash) => wallet
+            .valid_pending_transaction(&tx_hash)
+            .await
+            .map(|fee| (tx_hash, fee)),
+        None => None,
+    };
+
+    let (tx_hash, fee) = match cached {
+        Some(cached) => cached,
+        None => {
+            // the nonce went stale, or the app was restarted, build it again
+            for (item, _) in batch.iter() {
+                if let Some(tx_hash) = &item.tx_hash {
+                    let _ = wallet.discard_transaction(tx_hash).await;
                 }
+            }
+
+            let mut db_messages: Vec<&mut DbMessage> =
+                batch.iter_mut().map(|(_, db_message)| db_message).collect();
+            let (transfers, _, _) = messages_transfers(wallet, &mut db_messages).await?;
+            let transaction_summary = wallet.create_transfers_transaction(transfers).await?;
+            (transaction_summary.hash, transaction_summary.fee)
+        }
+    };
+
+    if let Err(e) = wallet.broadcast_transaction(tx_hash.clone()).await {
+        // kept with the messages, the next attempt first checks it did not go through
+        for (_, db_message) in batch.iter_mut() {
+            db_message.hash = tx_hash.clone();
+        }
+
+        // built again on the next attempt, its nonce may be taken by then,
+        // the transactions built on it are built again too
+        let _ = wallet.discard_transaction(&tx_hash).await;
+        return Err(e);
+    }
+    wallet.state.sent_tx_hashes.push(tx_hash.clone());
+    let status = broadcast_status(wallet, &tx_hash).await;
+
+    // the network fee is shared by the messages of the batch
+    let fee_share =
+        format_xelis(fee).parse::<f64>().unwrap_or_default() / batch.len().max(1) as f64;
+    for (_, db_message) in batch.iter_mut() {
+        db_message.set_status(MessageStatus::Broadcast);
+        db_message.set_status(status);
+        db_message.hash = tx_hash.clone();
+        db_message.fee = fee_share;
+    }
+
+    Ok(tx_hash)
+}
+
+/// Sends the outbox messages that are due, failing them after too many attempts.
+/// With batching enabled the due messages share transactions.
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_process_outbox() {
+    let Some(account_id) = *ACCOUNT.read() else {
+        return;
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let batch_messages = SETTINGS.read().batch_messages;
+
+    let items = match &*DB.read() {
+        Some(db) => match db_read_due_outbox(db, account_id, now).await {
+            Ok(items) => items,
+            Err(e) => {
+                info!("Outbox read error: {e}");
+                return;
+            }
+        },
+        None => return,
+    };
+
+    let mut entries = Vec::new();
+    for item in items {
+        let db_message = match &*DB.read() {
+            Some(db) => match db_read_message(db, account_id, &item.local_id).await {
+                Ok(db_message) => db_message,
                 Err(e) => {
-                    info!("{}", e);
-                    info.set(format!("error_2: {}", e).to_string());
+                    info!("Outbox message {} error: {e}", item.local_id);
+                    let _ = db_remove_outbox(db, account_id, &item.local_id).await;
+                    continue;
                 }
-            };
+            },
+            None => return,
+        };
+
+        // the last attempt may have gone through, the message is never sent twice
+        match sent_message_state(&db_message).await {
+            Ok(None | Some(SentTxState::Dropped)) => entries.push((item, db_message)),
+            Ok(Some(state)) => {
+                info!(
+                    "Outbox message {} went through in {}",
+                    item.local_id, db_message.hash
+                );
+                if let Some(db) = &*DB.read() {
+                    let _ = db_remove_outbox(db, account_id, &item.local_id).await;
+                }
+                settle_sent_message(db_message, state).await;
+                publish_app_event(AppEvent::MessagesChanged);
+            }
+            // checked again on the next run
+            Err(e) => info!("Outbox message {} cannot be checked: {e}", item.local_id),
         }
-        None => {
-            info!("Error reading wallet");
+    }
+
+    for mut batch in outbox_batches(entries, batch_messages) {
+        let result = match &*WALLET.read() {
+            Some(wallet) => {
+                let mut wallet = wallet.write().await;
+                if !wallet.is_online().await {
+                    // offline is not an attempt, wait for the connection
+                    return;
+                }
+
+                // a message built for the user to confirm goes first, waiting is not an attempt
+                let cached_tx_hash = batch.first().and_then(|(item, _)| item.tx_hash.clone());
+                match wallet.next_pending_transaction().await {
+                    Some(next) if Some(&next) != cached_tx_hash.as_ref() => return,
+                    _ => wallet_send_outbox_batch(&mut wallet, &mut batch).await,
+                }
+            }
+            None => return,
+        };
+
+        for (item, db_message) in batch.iter() {
+            let stored = match (&*DB.read(), &result) {
+                (None, _) => return,
+                (Some(db), Ok(tx_hash)) => {
+                    info!("Outbox message {} sent in {tx_hash}", item.local_id);
+                    db_outbox_sent(db, account_id, db_message).await
+                }
+                (Some(db), Err(e)) => {
+                    info!("Outbox message {} failed: {e}", item.local_id);
+                    let attempts = item.attempts + 1;
+
+                    if attempts >= OUTBOX_MAX_ATTEMPTS {
+                        db_outbox_failed(db, account_id, db_message).await
+                    } else {
+                        db_outbox_retry_later(
+                            db,
+                            account_id,
+                            db_message,
+                            attempts,
+                            now + outbox_backoff(attempts),
+                            &e.to_string(),
+                        )
+                        .await
+                    }
+                }
+            };
+
+            if let Err(e) = stored {
+                info!("Outbox update error: {e}");
+            }
         }
+
+        publish_app_event(AppEvent::MessagesChanged);
+    }
+}
+
+/// Drops a built message the user did not confirm, with the transactions built after it.
+/// Returns the hashes of the dropped transactions.
+#[allow(clippy::await_holding_invalid_type)]
+pub async fn wallet_cancel_message(pending_send: PendingSend) -> Vec<String> {
+    match &*WALLET.read() {
+        Some(wallet) => match wallet
+            .read()
+            .await
+            .discard_transaction(&pending_send.tx_hash)
+            .await
+        {
+            Ok(discarded) => discarded,
+            Err(e) => {
+                info!("{e}");
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
     }
 }